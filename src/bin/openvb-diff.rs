@@ -0,0 +1,35 @@
+// Prints the differences between two versions of a singer, and optionally writes them as a patch.
+//
+//     openvb-diff <old> <new> [--threshold 1ms] [--patch patch.json]
+
+use anyhow::{Result, bail};
+
+use openvb::prelude::*;
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let mut paths = vec![];
+    let mut threshold = Timestamp::from_milliseconds(1.0);
+    let mut patch = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threshold" => threshold = args.next().unwrap_or_default().parse()?,
+            "--patch" => patch = args.next(),
+            _ => paths.push(arg)
+        }
+    }
+
+    let [old, new] = &paths[..] else {
+        bail!("Usage: openvb-diff <old> <new> [--threshold 1ms] [--patch patch.json]");
+    };
+
+    let diff = SingerDiff::between(&Singer::load(old)?, &Singer::load(new)?, threshold);
+    print!("{}", diff);
+
+    if let Some(path) = patch {
+        diff.save(path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,488 @@
+// Structural differences between two versions of a singer. Libraries are matched by Uuid, or by name while their
+// Uuid is still nil as generated, and files by path;
+// aliases and label timings are compared, other fields are not. A diff doubles as a patch for the older version:
+// it records the old values next to the new ones and refuses to apply where they no longer match.
+
+use std::{fmt::{self, Display}, path::{Path, PathBuf}};
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{library::Library, time::{TimeRange, Timestamp}, utterance::{FileDescriptor, Utterance}, Singer};
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct SingerDiff {
+    pub added_libraries: Vec<Library>,
+    // Uuid and name of every library that is gone
+    pub removed_libraries: Vec<(Uuid, String)>,
+    pub libraries: Vec<LibraryDiff>
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryDiff {
+    pub uuid: Uuid,
+    pub name: String,
+    pub added_files: Vec<FileDescriptor>,
+    pub removed_files: Vec<PathBuf>,
+    pub changed_files: Vec<FileDiff>
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    // Old and new aliases
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<(Vec<String>, Vec<String>)>,
+    // Old and new labels, when phonemes were added, removed or changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relabeled: Option<(Vec<Utterance>, Vec<Utterance>)>,
+    // Timing changes of labels that kept their phoneme
+    pub shifts: Vec<LabelShift>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelShift {
+    pub index: usize,
+    pub phoneme: String,
+    pub old: TimeRange,
+    pub new: TimeRange
+}
+
+impl LabelShift {
+    // Differences new - old
+    pub fn start(&self) -> Timestamp {
+        self.new.start - self.old.start
+    }
+
+    pub fn end(&self) -> Timestamp {
+        self.new.end - self.old.end
+    }
+}
+
+impl SingerDiff {
+    // Label boundaries that moved by no more than `threshold` are not reported
+    pub fn between(old: &Singer, new: &Singer, threshold: Timestamp) -> Self {
+        let mut diff = SingerDiff::default();
+
+        for library in old.libraries.iter() {
+            if !new.libraries.iter().any(|l| same_library(l, library.uuid, &library.name)) {
+                diff.removed_libraries.push((library.uuid, library.name.clone()));
+            }
+        }
+
+        for library in new.libraries.iter() {
+            match old.libraries.iter().find(|l| same_library(l, library.uuid, &library.name)) {
+                Some(previous) => {
+                    let library_diff = LibraryDiff::between(previous, library, threshold);
+                    if !library_diff.is_empty() {
+                        diff.libraries.push(library_diff);
+                    }
+                },
+                None => diff.added_libraries.push(library.clone())
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_libraries.is_empty() && self.removed_libraries.is_empty() && self.libraries.is_empty()
+    }
+
+    // Fails without changing the singer if it is not the version the diff was made from
+    pub fn apply(&self, singer: &mut Singer) -> Result<()> {
+        let mut libraries = singer.libraries.clone();
+
+        for (uuid, name) in self.removed_libraries.iter() {
+            let i = libraries.iter().position(|l| same_library(l, *uuid, name)).ok_or(anyhow!("Library {} ({}) does not exist", name, uuid))?;
+            libraries.remove(i);
+        }
+
+        for library_diff in self.libraries.iter() {
+            let library = libraries.iter_mut()
+                .find(|l| same_library(l, library_diff.uuid, &library_diff.name))
+                .ok_or(anyhow!("Library {} ({}) does not exist", library_diff.name, library_diff.uuid))?;
+
+            library_diff.apply(library)?;
+        }
+
+        for library in self.added_libraries.iter() {
+            if libraries.iter().any(|l| same_library(l, library.uuid, &library.name)) {
+                bail!("Library {} ({}) already exists", library.name, library.uuid);
+            }
+
+            libraries.push(library.clone());
+        }
+
+        singer.libraries = libraries;
+
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl LibraryDiff {
+    pub fn between(old: &Library, new: &Library, threshold: Timestamp) -> Self {
+        let mut diff = LibraryDiff {
+            uuid: new.uuid,
+            name: new.name.clone(),
+            ..Default::default()
+        };
+
+        for file in old.files.iter() {
+            if !new.files.iter().any(|f| f.path == file.path) {
+                diff.removed_files.push(file.path.clone());
+            }
+        }
+
+        for file in new.files.iter() {
+            match old.files.iter().find(|f| f.path == file.path) {
+                Some(previous) => {
+                    let file_diff = FileDiff::between(previous, file, threshold);
+                    if !file_diff.is_empty() {
+                        diff.changed_files.push(file_diff);
+                    }
+                },
+                None => diff.added_files.push(file.clone())
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_files.is_empty() && self.removed_files.is_empty() && self.changed_files.is_empty()
+    }
+
+    // Fails without changing the library if it is not the version the diff was made from
+    pub fn apply(&self, library: &mut Library) -> Result<()> {
+        let mut files = library.files.clone();
+
+        for path in self.removed_files.iter() {
+            let i = files.iter().position(|f| f.path == *path).ok_or(anyhow!("File {:?} does not exist", path))?;
+            files.remove(i);
+        }
+
+        for file_diff in self.changed_files.iter() {
+            let file = files.iter_mut()
+                .find(|f| f.path == file_diff.path)
+                .ok_or(anyhow!("File {:?} does not exist", file_diff.path))?;
+
+            file_diff.apply(file)?;
+        }
+
+        for file in self.added_files.iter() {
+            if files.iter().any(|f| f.path == file.path) {
+                bail!("File {:?} already exists", file.path);
+            }
+
+            files.push(file.clone());
+        }
+
+        library.files = files;
+
+        Ok(())
+    }
+}
+
+impl FileDiff {
+    pub fn between(old: &FileDescriptor, new: &FileDescriptor, threshold: Timestamp) -> Self {
+        let mut diff = FileDiff {
+            path: new.path.clone(),
+            aliases: Some((old.aliases.clone(), new.aliases.clone())).filter(|(a, b)| a != b),
+            ..Default::default()
+        };
+
+        let same_phonemes = old.labels.len() == new.labels.len() && old.labels.iter().zip(new.labels.iter())
+            .all(|(a, b)| (&a.prev, &a.curr, &a.next) == (&b.prev, &b.curr, &b.next));
+
+        if !same_phonemes {
+            diff.relabeled = Some((old.labels.clone(), new.labels.clone()));
+            return diff;
+        }
+
+        for (index, (a, b)) in old.labels.iter().zip(new.labels.iter()).enumerate() {
+            let shift = LabelShift {
+                index,
+                phoneme: b.curr.clone(),
+                old: a.range(),
+                new: b.range()
+            };
+
+            if shift.start().abs() > threshold || shift.end().abs() > threshold {
+                diff.shifts.push(shift);
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_none() && self.relabeled.is_none() && self.shifts.is_empty()
+    }
+
+    // Checks every old value before writing, so a file is either fully patched or left alone
+    pub fn apply(&self, file: &mut FileDescriptor) -> Result<()> {
+        if let Some((old, _)) = &self.aliases {
+            if file.aliases != *old {
+                bail!("Aliases of {:?} are {:?}, expected {:?}", self.path, file.aliases, old);
+            }
+        }
+
+        if let Some((old, _)) = &self.relabeled {
+            if !same_labels(&file.labels, old) {
+                bail!("Labels of {:?} changed since the diff was made", self.path);
+            }
+        }
+
+        for shift in self.shifts.iter() {
+            let label = file.labels.get(shift.index).filter(|l| l.curr == shift.phoneme);
+            let Some(label) = label else {
+                bail!("Label {} of {:?} is not {}", shift.index, self.path, shift.phoneme);
+            };
+
+            if label.range() != shift.old {
+                bail!("Label {} of {:?} spans {:?}, expected {:?}", shift.index, self.path, label.range(), shift.old);
+            }
+        }
+
+        if let Some((_, new)) = &self.aliases {
+            file.aliases = new.clone();
+        }
+
+        if let Some((_, new)) = &self.relabeled {
+            file.labels = new.clone();
+        }
+
+        for shift in self.shifts.iter() {
+            file.labels[shift.index].set_range(shift.new);
+        }
+
+        Ok(())
+    }
+}
+
+// Generated libraries all start out with the nil Uuid, so it says nothing about identity
+fn same_library(library: &Library, uuid: Uuid, name: &str) -> bool {
+    if uuid.is_nil() {
+        library.uuid.is_nil() && library.name == name
+    } else {
+        library.uuid == uuid
+    }
+}
+
+// Phonemes and timings only; the audio path is filled in on load and depends on where the singer lives
+fn same_labels(a: &[Utterance], b: &[Utterance]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter())
+        .all(|(a, b)| (&a.prev, &a.curr, &a.next, a.range()) == (&b.prev, &b.curr, &b.next, b.range()))
+}
+
+fn signed(time: Timestamp) -> String {
+    if time.value > 0 { format!("+{}", time) } else { time.to_string() }
+}
+
+// One line per change, prefixed with `+` (added), `-` (removed) or `~` (changed)
+impl Display for SingerDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for library in self.added_libraries.iter() {
+            writeln!(f, "+ library {} ({} files)", library.name, library.files.len())?;
+        }
+
+        for (_, name) in self.removed_libraries.iter() {
+            writeln!(f, "- library {}", name)?;
+        }
+
+        for library in self.libraries.iter() {
+            writeln!(f, "~ library {}", library.name)?;
+
+            for file in library.added_files.iter() {
+                writeln!(f, "  + {}", file.path.display())?;
+            }
+
+            for path in library.removed_files.iter() {
+                writeln!(f, "  - {}", path.display())?;
+            }
+
+            for file in library.changed_files.iter() {
+                let path = file.path.display();
+
+                if let Some((_, aliases)) = &file.aliases {
+                    writeln!(f, "  ~ {} aliases: {}", path, aliases.join(", "))?;
+                }
+
+                if let Some((_, labels)) = &file.relabeled {
+                    let phonemes = labels.iter().map(|l| l.curr.as_str()).collect::<Vec<&str>>();
+                    writeln!(f, "  ~ {} relabeled: {}", path, phonemes.join(" "))?;
+                }
+
+                for shift in file.shifts.iter() {
+                    writeln!(f, "  ~ {} #{} {}: start {}, end {}", path, shift.index, shift.phoneme, signed(shift.start()), signed(shift.end()))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(curr: &str, start: i64, end: i64) -> Utterance {
+        Utterance {
+            curr: curr.to_string(),
+            start: Timestamp::new(start),
+            end: Timestamp::new(end),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_and_patch() {
+        let old = Singer {
+            libraries: vec![Library {
+                name: String::from("Normal"),
+                uuid: Uuid::new_v4(),
+                files: vec![
+                    FileDescriptor {
+                        path: PathBuf::from("ka.wav"),
+                        aliases: vec![String::from("ka")],
+                        labels: vec![label("k", 0, 100_000), label("a", 100_000, 400_000)],
+                        ..Default::default()
+                    },
+                    FileDescriptor {
+                        path: PathBuf::from("sa.wav"),
+                        ..Default::default()
+                    }
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut new = old.clone();
+        let library = &mut new.libraries[0];
+        library.files[0].aliases.push(String::from("か"));
+        library.files[0].labels[0].end = Timestamp::new(112_000);
+        library.files[0].labels[1].start = Timestamp::new(100_500);
+        library.files.remove(1);
+        library.files.push(FileDescriptor {
+            path: PathBuf::from("ta.wav"),
+            ..Default::default()
+        });
+
+        let diff = SingerDiff::between(&old, &new, Timestamp::from_milliseconds(1.0));
+        let library_diff = &diff.libraries[0];
+        assert_eq!(library_diff.removed_files, vec![PathBuf::from("sa.wav")]);
+        assert_eq!(library_diff.added_files.len(), 1);
+        assert_eq!(library_diff.changed_files[0].shifts.len(), 1);
+
+        let output = diff.to_string();
+        assert!(output.contains("  ~ ka.wav #0 k: start 0µs, end +12.000ms"));
+        assert!(output.contains("  - sa.wav"));
+
+        // The sub-threshold shift of "a" is not part of the patch
+        let mut patched = old.clone();
+        let json = serde_json::to_string(&diff).unwrap();
+        serde_json::from_str::<SingerDiff>(&json).unwrap().apply(&mut patched).unwrap();
+        assert_eq!(patched.libraries[0].files[0].labels[0], new.libraries[0].files[0].labels[0]);
+        assert_eq!(patched.libraries[0].files[0].labels[1].start, Timestamp::new(100_000));
+        assert!(SingerDiff::between(&patched, &new, Timestamp::from_milliseconds(1.0)).is_empty());
+
+        // Libraries with nil Uuids are told apart by name
+        let unnamed = |name: &str| Library {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let before = Singer {
+            libraries: vec![unnamed("Normal")],
+            ..Default::default()
+        };
+        let after = Singer {
+            libraries: vec![unnamed("Whisper")],
+            ..Default::default()
+        };
+        let renamed = SingerDiff::between(&before, &after, Timestamp::default());
+        assert_eq!((renamed.added_libraries.len(), renamed.removed_libraries.len()), (1, 1));
+
+        // Patching twice fails instead of doubling the changes
+        assert!(diff.apply(&mut patched).is_err());
+
+        // So does a shift on a file whose labels moved since the diff was made
+        let file_diff = &diff.libraries[0].changed_files[0];
+        let mut file = old.libraries[0].files[0].clone();
+        file.aliases = file_diff.aliases.clone().unwrap().1;
+        assert!(file_diff.apply(&mut file).is_err());
+        file.aliases = old.libraries[0].files[0].aliases.clone();
+        file.labels[0].start = Timestamp::new(5_000);
+        assert!(file_diff.apply(&mut file).is_err());
+        assert_eq!(file.labels[0].end, Timestamp::new(100_000));
+    }
+
+    #[test]
+    fn test_patch_twice() {
+        let file = |path: &str, labels: Vec<Utterance>| FileDescriptor {
+            path: PathBuf::from(path),
+            labels,
+            ..Default::default()
+        };
+
+        let old = Singer {
+            libraries: vec![Library {
+                name: String::from("Normal"),
+                uuid: Uuid::new_v4(),
+                files: vec![file("ka.wav", vec![label("k", 0, 100_000), label("a", 100_000, 400_000)])],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        // Only additions: a new file and a new library
+        let mut new = old.clone();
+        new.libraries[0].files.push(file("sa.wav", vec![]));
+        new.libraries.push(Library {
+            name: String::from("Whisper"),
+            uuid: Uuid::new_v4(),
+            ..Default::default()
+        });
+
+        let diff = SingerDiff::between(&old, &new, Timestamp::default());
+        let mut patched = old.clone();
+        diff.apply(&mut patched).unwrap();
+        assert!(diff.apply(&mut patched).is_err());
+        assert_eq!(patched.libraries.len(), 2);
+
+        let mut library = old.libraries[0].clone();
+        diff.libraries[0].apply(&mut library).unwrap();
+        assert!(diff.libraries[0].apply(&mut library).is_err());
+        assert_eq!(library.files.len(), 2);
+
+        // Relabeling checks the labels it replaces
+        let mut relabeled = old.clone();
+        relabeled.libraries[0].files[0].labels = vec![label("k", 0, 100_000), label("i", 100_000, 400_000)];
+        let diff = SingerDiff::between(&old, &relabeled, Timestamp::default());
+        let mut patched = old.clone();
+        diff.apply(&mut patched).unwrap();
+        assert!(diff.apply(&mut patched).is_err());
+
+        // A failed library patch leaves the library as it was
+        let mut library_diff = diff.libraries[0].clone();
+        library_diff.removed_files.push(PathBuf::from("sa.wav"));
+        let mut library = old.libraries[0].clone();
+        library.files.push(file("sa.wav", vec![]));
+        library.files[0].labels[1].curr = String::from("o");
+        assert!(library_diff.apply(&mut library).is_err());
+        assert_eq!(library.files.len(), 2);
+    }
+}
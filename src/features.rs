@@ -0,0 +1,416 @@
+// Articulatory features of the X-SAMPA symbols in `PHONEME_DATA` and of the affricates and diphthongs the phoneset
+// converters produce, for judging how close two phonemes are.
+// Consonants are described by place, manner and voicing, vowels by height, backness and rounding.
+
+use std::collections::BTreeSet;
+
+use crate::{index::UtteranceIndex, library::Library};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Place {
+    Bilabial,
+    Labiodental,
+    Dental,
+    Alveolar,
+    Postalveolar,
+    Retroflex,
+    AlveoloPalatal,
+    Palatal,
+    LabialPalatal,
+    Velar,
+    LabialVelar,
+    Uvular,
+    Pharyngeal,
+    Epiglottal,
+    Glottal
+}
+
+impl Place {
+    // Position along the vocal tract; labialized places share a position with their plain counterpart
+    fn position(&self) -> i32 {
+        match self {
+            Place::Bilabial => 0,
+            Place::Labiodental => 1,
+            Place::Dental => 2,
+            Place::Alveolar => 3,
+            Place::Postalveolar => 4,
+            Place::Retroflex => 5,
+            Place::AlveoloPalatal => 6,
+            Place::Palatal | Place::LabialPalatal => 7,
+            Place::Velar | Place::LabialVelar => 8,
+            Place::Uvular => 9,
+            Place::Pharyngeal => 10,
+            Place::Epiglottal => 11,
+            Place::Glottal => 12
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Manner {
+    Plosive,
+    Affricate,
+    Implosive,
+    Nasal,
+    Trill,
+    Tap,
+    LateralTap,
+    Fricative,
+    LateralFricative,
+    Approximant,
+    LateralApproximant,
+    Click,
+    Vowel
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Height {
+    Close,
+    NearClose,
+    CloseMid,
+    Mid,
+    OpenMid,
+    NearOpen,
+    Open
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Backness {
+    Front,
+    Central,
+    Back
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PhoneticFeatures {
+    pub manner: Manner,
+    // Consonants only
+    pub place: Option<Place>,
+    pub voiced: bool,
+    // Vowels only
+    pub height: Option<Height>,
+    pub backness: Option<Backness>,
+    pub rounded: bool
+}
+
+const fn consonant(place: Place, manner: Manner, voiced: bool) -> PhoneticFeatures {
+    PhoneticFeatures {
+        manner,
+        place: Some(place),
+        voiced,
+        height: None,
+        backness: None,
+        rounded: false
+    }
+}
+
+const fn vowel(height: Height, backness: Backness, rounded: bool) -> PhoneticFeatures {
+    PhoneticFeatures {
+        manner: Manner::Vowel,
+        place: None,
+        voiced: true,
+        height: Some(height),
+        backness: Some(backness),
+        rounded
+    }
+}
+
+impl PhoneticFeatures {
+    // Looks up an X-SAMPA symbol. Diacritics and length marks are ignored, except `_0` and `_v` which change voicing.
+    pub fn of(symbol: &str) -> Option<PhoneticFeatures> {
+        if let Some(features) = base_features(symbol) {
+            return Some(features);
+        }
+
+        let (base, diacritics) = symbol.split_once('_').unwrap_or((symbol, ""));
+        let base = base.trim_end_matches(":\\").trim_end_matches([':', '~', '=']);
+        let mut features = base_features(base)?;
+
+        if diacritics.contains('0') {
+            features.voiced = false;
+        } else if diacritics.contains('v') {
+            features.voiced = true;
+        }
+
+        Some(features)
+    }
+
+    pub fn is_vowel(&self) -> bool {
+        self.manner == Manner::Vowel
+    }
+
+    // 0 for identical features. Vowels differ by up to 1 per height, backness and rounding, consonants by up to
+    // 1 per place and manner and 0.5 for voicing; a vowel and a consonant are always 2.5 apart.
+    pub fn distance(&self, other: &PhoneticFeatures) -> f64 {
+        match (self.is_vowel(), other.is_vowel()) {
+            (true, true) => {
+                let height = (self.height.unwrap() as i32 - other.height.unwrap() as i32).abs() as f64 / 6.0;
+                let backness = (self.backness.unwrap() as i32 - other.backness.unwrap() as i32).abs() as f64 / 2.0;
+                let rounded = if self.rounded != other.rounded { 0.5 } else { 0.0 };
+
+                height + backness + rounded
+            },
+            (false, false) => {
+                let place = (self.place.unwrap().position() - other.place.unwrap().position()).abs() as f64 / 4.0;
+                let manner = if self.manner != other.manner { 1.0 } else { 0.0 };
+                let voiced = if self.voiced != other.voiced { 0.5 } else { 0.0 };
+
+                place.min(1.0) + manner + voiced
+            },
+            _ => 2.5
+        }
+    }
+}
+
+// Distance between two X-SAMPA symbols, or None if either is not a known segment
+pub fn phoneme_distance(a: &str, b: &str) -> Option<f64> {
+    Some(PhoneticFeatures::of(a)?.distance(&PhoneticFeatures::of(b)?))
+}
+
+// The candidate closest to `symbol`, with its distance. `symbol` itself is skipped, so this finds the closest
+// substitute; unknown candidates are skipped too.
+pub fn nearest_phoneme<'a, I: IntoIterator<Item = &'a str>>(symbol: &str, candidates: I) -> Option<(&'a str, f64)> {
+    let features = PhoneticFeatures::of(symbol)?;
+
+    candidates.into_iter()
+        .filter(|c| *c != symbol)
+        .filter_map(|c| PhoneticFeatures::of(c).map(|f| (c, features.distance(&f))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct FeatureQuery {
+    pub manner: Option<Manner>,
+    pub place: Option<Place>,
+    pub voiced: Option<bool>,
+    pub height: Option<Height>,
+    pub backness: Option<Backness>,
+    pub rounded: Option<bool>
+}
+
+impl FeatureQuery {
+    pub fn matches(&self, features: &PhoneticFeatures) -> bool {
+        self.manner.is_none_or(|m| m == features.manner)
+            && self.place.is_none_or(|p| Some(p) == features.place)
+            && self.voiced.is_none_or(|v| v == features.voiced)
+            && self.height.is_none_or(|h| Some(h) == features.height)
+            && self.backness.is_none_or(|b| Some(b) == features.backness)
+            && self.rounded.is_none_or(|r| r == features.rounded)
+    }
+
+    // Distinct phonemes of the library that match, e.g. every voiced plosive
+    pub fn phonemes<'a>(&self, library: &'a Library) -> BTreeSet<&'a str> {
+        library.iter_labels()
+            .map(|l| l.curr.as_str())
+            .filter(|p| PhoneticFeatures::of(p).is_some_and(|f| self.matches(&f)))
+            .collect()
+    }
+
+    pub fn phonemes_in<'a>(&self, index: &UtteranceIndex<'a>) -> BTreeSet<&'a str> {
+        index.phonemes()
+            .filter(|p| PhoneticFeatures::of(p).is_some_and(|f| self.matches(&f)))
+            .collect()
+    }
+}
+
+fn base_features(symbol: &str) -> Option<PhoneticFeatures> {
+    use Backness::*;
+    use Height::*;
+    use Manner::*;
+    use Place::*;
+
+    let features = match symbol {
+        // Plosives
+        "p" => consonant(Bilabial, Plosive, false),
+        "b" => consonant(Bilabial, Plosive, true),
+        "t" => consonant(Alveolar, Plosive, false),
+        "d" => consonant(Alveolar, Plosive, true),
+        "t`" => consonant(Retroflex, Plosive, false),
+        "d`" => consonant(Retroflex, Plosive, true),
+        "c" => consonant(Palatal, Plosive, false),
+        "J\\" => consonant(Palatal, Plosive, true),
+        "k" => consonant(Velar, Plosive, false),
+        "g" => consonant(Velar, Plosive, true),
+        "q" => consonant(Uvular, Plosive, false),
+        "G\\" => consonant(Uvular, Plosive, true),
+        ">\\" => consonant(Epiglottal, Plosive, false),
+        "?" => consonant(Glottal, Plosive, false),
+
+        // Implosives
+        "b_<" => consonant(Bilabial, Implosive, true),
+        "d_<" => consonant(Alveolar, Implosive, true),
+        "J\\_<" => consonant(Palatal, Implosive, true),
+        "g_<" => consonant(Velar, Implosive, true),
+        "G\\_<" => consonant(Uvular, Implosive, true),
+
+        // Affricates
+        "ts" => consonant(Alveolar, Affricate, false),
+        "dz" => consonant(Alveolar, Affricate, true),
+        "tS" => consonant(Postalveolar, Affricate, false),
+        "dZ" => consonant(Postalveolar, Affricate, true),
+        "ts\\" => consonant(AlveoloPalatal, Affricate, false),
+        "dz\\" => consonant(AlveoloPalatal, Affricate, true),
+
+        // Nasals
+        "m" => consonant(Bilabial, Nasal, true),
+        "F" => consonant(Labiodental, Nasal, true),
+        "n" => consonant(Alveolar, Nasal, true),
+        "n`" => consonant(Retroflex, Nasal, true),
+        "J" => consonant(Palatal, Nasal, true),
+        "N" => consonant(Velar, Nasal, true),
+        "N\\" => consonant(Uvular, Nasal, true),
+
+        // Trills and taps
+        "B\\" => consonant(Bilabial, Trill, true),
+        "r" => consonant(Alveolar, Trill, true),
+        "R\\" => consonant(Uvular, Trill, true),
+        "4" => consonant(Alveolar, Tap, true),
+        "r`" => consonant(Retroflex, Tap, true),
+        "l\\" => consonant(Alveolar, LateralTap, true),
+
+        // Fricatives
+        "p\\" => consonant(Bilabial, Fricative, false),
+        "B" => consonant(Bilabial, Fricative, true),
+        "f" => consonant(Labiodental, Fricative, false),
+        "v" => consonant(Labiodental, Fricative, true),
+        "T" => consonant(Dental, Fricative, false),
+        "D" => consonant(Dental, Fricative, true),
+        "s" => consonant(Alveolar, Fricative, false),
+        "z" => consonant(Alveolar, Fricative, true),
+        "S" => consonant(Postalveolar, Fricative, false),
+        "Z" => consonant(Postalveolar, Fricative, true),
+        "s`" => consonant(Retroflex, Fricative, false),
+        "z`" => consonant(Retroflex, Fricative, true),
+        "s\\" => consonant(AlveoloPalatal, Fricative, false),
+        "z\\" => consonant(AlveoloPalatal, Fricative, true),
+        "C" => consonant(Palatal, Fricative, false),
+        "j\\" => consonant(Palatal, Fricative, true),
+        "x" => consonant(Velar, Fricative, false),
+        "G" => consonant(Velar, Fricative, true),
+        "x\\" => consonant(Velar, Fricative, false),
+        "W" => consonant(LabialVelar, Fricative, false),
+        "X" => consonant(Uvular, Fricative, false),
+        "R" => consonant(Uvular, Fricative, true),
+        "X\\" => consonant(Pharyngeal, Fricative, false),
+        "?\\" => consonant(Pharyngeal, Fricative, true),
+        "H\\" => consonant(Epiglottal, Fricative, false),
+        "<\\" => consonant(Epiglottal, Fricative, true),
+        "h" => consonant(Glottal, Fricative, false),
+        "h\\" => consonant(Glottal, Fricative, true),
+        "K" => consonant(Alveolar, LateralFricative, false),
+        "K\\" => consonant(Alveolar, LateralFricative, true),
+
+        // Approximants
+        "v\\" | "P" => consonant(Labiodental, Approximant, true),
+        "r\\" => consonant(Alveolar, Approximant, true),
+        "r\\`" => consonant(Retroflex, Approximant, true),
+        "j" => consonant(Palatal, Approximant, true),
+        "H" => consonant(LabialPalatal, Approximant, true),
+        "M\\" => consonant(Velar, Approximant, true),
+        "w" => consonant(LabialVelar, Approximant, true),
+        "l" | "5" => consonant(Alveolar, LateralApproximant, true),
+        "l`" => consonant(Retroflex, LateralApproximant, true),
+        "L" => consonant(Palatal, LateralApproximant, true),
+        "L\\" => consonant(Velar, LateralApproximant, true),
+
+        // Clicks
+        "O\\" => consonant(Bilabial, Click, false),
+        "|\\" => consonant(Dental, Click, false),
+        "|\\|\\" => consonant(Alveolar, Click, false),
+        "!\\" => consonant(Postalveolar, Click, false),
+        "=\\" => consonant(Palatal, Click, false),
+
+        // Vowels
+        "i" => vowel(Close, Front, false),
+        "y" => vowel(Close, Front, true),
+        "1" => vowel(Close, Central, false),
+        "}" => vowel(Close, Central, true),
+        "M" => vowel(Close, Back, false),
+        "u" => vowel(Close, Back, true),
+        "I" => vowel(NearClose, Front, false),
+        "Y" => vowel(NearClose, Front, true),
+        "I\\" => vowel(NearClose, Central, false),
+        "U\\" => vowel(NearClose, Central, true),
+        "U" => vowel(NearClose, Back, true),
+        "e" => vowel(CloseMid, Front, false),
+        "2" => vowel(CloseMid, Front, true),
+        "@\\" => vowel(CloseMid, Central, false),
+        "8" => vowel(CloseMid, Central, true),
+        "7" => vowel(CloseMid, Back, false),
+        "o" => vowel(CloseMid, Back, true),
+        "@" | "@`" => vowel(Mid, Central, false),
+        "E" => vowel(OpenMid, Front, false),
+        "9" => vowel(OpenMid, Front, true),
+        "3" | "3`" => vowel(OpenMid, Central, false),
+        "3\\" => vowel(OpenMid, Central, true),
+        "V" => vowel(OpenMid, Back, false),
+        "O" => vowel(OpenMid, Back, true),
+        "{" => vowel(NearOpen, Front, false),
+        "6" => vowel(NearOpen, Central, false),
+        "a" => vowel(Open, Front, false),
+        "&" => vowel(Open, Front, true),
+        "A" => vowel(Open, Back, false),
+        "Q" => vowel(Open, Back, true),
+
+        // Diphthongs, by their first element
+        "aI" | "aU" => vowel(Open, Front, false),
+        "eI" | "eU" => vowel(CloseMid, Front, false),
+        "oU" => vowel(CloseMid, Back, true),
+        "OI" => vowel(OpenMid, Back, true),
+
+        _ => return None
+    };
+
+    Some(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{encode::PHONEME_DATA, utterance::{FileDescriptor, Utterance}};
+
+    // Symbols in `PHONEME_DATA` that are not segments: silences, reserved slots, suprasegmentals and tone marks
+    const NON_SEGMENTAL: [&str; 21] = [
+        "sil", "br", "pau", "cl", "<RES>", ".", "\"", "%", "'", ":", ":\\", "-", "*", "/", "<", ">", "^", "!", "|", "||", "-\\"
+    ];
+
+    #[test]
+    fn test_feature_table() {
+        for symbol in PHONEME_DATA {
+            assert!(PhoneticFeatures::of(symbol).is_some() != NON_SEGMENTAL.contains(&symbol), "{}", symbol);
+        }
+
+        // Everything the phoneset converters produce is a known segment
+        #[cfg(feature = "translate")]
+        for (_, symbol) in crate::tools::arpa::ARPA_DATA.iter().chain(crate::tools::czampa::CZAMPA_DATA.iter()) {
+            assert!(PhoneticFeatures::of(symbol).is_some(), "{}", symbol);
+        }
+
+        assert_eq!(phoneme_distance("b", "p"), Some(0.5));
+        assert!(phoneme_distance("e", "E").unwrap() < phoneme_distance("e", "a").unwrap());
+        assert!(phoneme_distance("s", "S").unwrap() < phoneme_distance("s", "k").unwrap());
+        assert_eq!(phoneme_distance("a", "sil"), None);
+        assert_eq!(PhoneticFeatures::of("z_0"), PhoneticFeatures::of("s"));
+        assert_eq!(nearest_phoneme("e", ["a", "o", "E", "k"]), Some(("E", 2.0 / 6.0)));
+
+        let labels = ["b", "a", "g", "t", "d_0"].iter().map(|c| Utterance {
+            curr: c.to_string(),
+            ..Default::default()
+        }).collect();
+        let library = Library {
+            files: vec![FileDescriptor {
+                labels,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let query = FeatureQuery {
+            manner: Some(Manner::Plosive),
+            voiced: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(query.phonemes(&library).into_iter().collect::<Vec<_>>(), vec!["b", "g"]);
+    }
+}
@@ -0,0 +1,240 @@
+// Queries for cutting a bank down to a subset, e.g. one language or pitch range for testing or training.
+// Subsets are owned copies with a fresh Uuid and the same `base_path`, so their relative paths still resolve.
+
+use std::{ops::RangeInclusive, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{index::IndexEntry, library::Library, merge::rebase, time::Timestamp, utterance::{Area, FileDescriptor, PhonemeFlags}, Singer};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LabelFilter {
+    Phoneme(String),
+    // Inclusive MIDI range; labels without a pitch never match
+    Pitch(RangeInclusive<u8>),
+    // The label's language, falling back to its file's and its library's
+    Language(String),
+    // All of the flag bits are set
+    Flags(u8),
+    // A field from `PhonemeFlags::field_names` is set; the only way to select the derived consonant field
+    Flag(String),
+    Extra { key: String, range: RangeInclusive<f32> },
+    Area(String),
+    Length(RangeInclusive<Timestamp>),
+    All(Vec<LabelFilter>),
+    Any(Vec<LabelFilter>),
+    Not(Box<LabelFilter>)
+}
+
+impl LabelFilter {
+    pub fn flags(flags: PhonemeFlags) -> Self {
+        LabelFilter::Flags(flags.bits())
+    }
+
+    pub fn area(area: Area) -> Self {
+        LabelFilter::Area(area.key().to_string())
+    }
+
+    pub fn matches(&self, entry: &IndexEntry) -> bool {
+        let label = entry.utterance;
+
+        match self {
+            LabelFilter::Phoneme(phoneme) => label.curr == *phoneme,
+            LabelFilter::Pitch(range) => label.pitch.is_some_and(|p| range.contains(&p)),
+            LabelFilter::Language(language) => entry.language() == Some(language.as_str()),
+            LabelFilter::Flags(flags) => label.flags & flags == *flags,
+            LabelFilter::Flag(name) => label.phoneme_flags().field(name) == Some(true),
+            LabelFilter::Extra { key, range } => label.extras.as_ref().and_then(|e| e.get(key)).is_some_and(|v| range.contains(v)),
+            LabelFilter::Area(key) => label.areas.as_ref().is_some_and(|a| a.contains_key(key)),
+            LabelFilter::Length(range) => range.contains(&label.length()),
+            LabelFilter::All(filters) => filters.iter().all(|f| f.matches(entry)),
+            LabelFilter::Any(filters) => filters.iter().any(|f| f.matches(entry)),
+            LabelFilter::Not(filter) => !filter.matches(entry)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FileFilter {
+    // The file's language, falling back to its library's
+    Language(String),
+    // Inclusive MIDI range; files without a pitch never match
+    Pitch(RangeInclusive<u8>),
+    Alias(String),
+    PathPrefix(PathBuf),
+    Extra { key: String, range: RangeInclusive<f32> },
+    // Every one of these phonemes occurs in the file's labels
+    Phonemes(Vec<String>),
+    AnyLabel(LabelFilter),
+    EveryLabel(LabelFilter),
+    All(Vec<FileFilter>),
+    Any(Vec<FileFilter>),
+    Not(Box<FileFilter>)
+}
+
+impl FileFilter {
+    pub fn matches(&self, library: &Library, file: &FileDescriptor) -> bool {
+        let entries = || file.labels.iter().map(|utterance| IndexEntry { library, file, utterance });
+
+        match self {
+            FileFilter::Language(language) => {
+                let file_language = file.language.as_deref().or(library.language.as_ref().map(|l| l.default.as_str()));
+                file_language == Some(language.as_str())
+            },
+            FileFilter::Pitch(range) => file.pitch.is_some_and(|p| range.contains(&p)),
+            FileFilter::Alias(alias) => file.aliases.contains(alias),
+            FileFilter::PathPrefix(prefix) => file.path.starts_with(prefix),
+            FileFilter::Extra { key, range } => file.extras.as_ref().and_then(|e| e.get(key)).is_some_and(|v| range.contains(v)),
+            FileFilter::Phonemes(phonemes) => phonemes.iter().all(|p| file.labels.iter().any(|l| l.curr == *p)),
+            FileFilter::AnyLabel(filter) => entries().any(|e| filter.matches(&e)),
+            FileFilter::EveryLabel(filter) => entries().all(|e| filter.matches(&e)),
+            FileFilter::All(filters) => filters.iter().all(|f| f.matches(library, file)),
+            FileFilter::Any(filters) => filters.iter().any(|f| f.matches(library, file)),
+            FileFilter::Not(filter) => !filter.matches(library, file)
+        }
+    }
+}
+
+impl Library {
+    // A copy holding only the matching files
+    pub fn subset(&self, filter: &FileFilter) -> Library {
+        Library {
+            uuid: Uuid::new_v4(),
+            files: self.files.iter().filter(|f| filter.matches(self, f)).cloned().collect(),
+            ..self.clone_empty()
+        }
+    }
+
+    // A copy holding only the matching labels. Files left without labels are dropped.
+    pub fn subset_labels(&self, filter: &LabelFilter) -> Library {
+        let files = self.files.iter().filter_map(|file| {
+            let labels = file.labels.iter()
+                .filter(|utterance| filter.matches(&IndexEntry { library: self, file, utterance }))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if labels.is_empty() {
+                return None;
+            }
+
+            Some(FileDescriptor {
+                labels,
+                ..file.clone()
+            })
+        }).collect();
+
+        Library {
+            uuid: Uuid::new_v4(),
+            files,
+            ..self.clone_empty()
+        }
+    }
+
+    // Moves the library to a new base path, keeping every file pointing at the same audio
+    pub fn rebase<P: AsRef<Path>>(&mut self, base_path: P) {
+        let base_path = base_path.as_ref();
+
+        for file in self.files.iter_mut() {
+            file.path = rebase(&file.path, &self.base_path, base_path);
+        }
+
+        self.base_path = base_path.to_path_buf();
+    }
+
+    fn clone_empty(&self) -> Library {
+        Library {
+            name: self.name.clone(),
+            uuid: self.uuid,
+            base_path: self.base_path.clone(),
+            language: self.language.clone(),
+            phoneset: self.phoneset.clone(),
+            is_default: self.is_default,
+            files: vec![],
+            flag_fields: self.flag_fields.clone(),
+            extra_fields: self.extra_fields.clone()
+        }
+    }
+}
+
+impl Singer {
+    // Applies the filter to every library, dropping libraries left empty
+    pub fn subset(&self, filter: &FileFilter) -> Singer {
+        self.with_libraries(self.libraries.iter().map(|l| l.subset(filter)))
+    }
+
+    pub fn subset_labels(&self, filter: &LabelFilter) -> Singer {
+        self.with_libraries(self.libraries.iter().map(|l| l.subset_labels(filter)))
+    }
+
+    fn with_libraries<I: Iterator<Item = Library>>(&self, libraries: I) -> Singer {
+        Singer {
+            meta: self.meta.clone(),
+            origin: self.origin.clone(),
+            language: self.language.clone(),
+            libraries: libraries.filter(|l| !l.files.is_empty()).collect(),
+            flag_fields: self.flag_fields.clone(),
+            extra_fields: self.extra_fields.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{singer::Language, utterance::Utterance};
+
+    fn file(path: &str, language: Option<&str>, pitch: u8, phonemes: &[&str]) -> FileDescriptor {
+        FileDescriptor {
+            path: PathBuf::from(path),
+            language: language.map(|l| l.to_string()),
+            pitch: Some(pitch),
+            labels: phonemes.iter().map(|p| Utterance {
+                curr: p.to_string(),
+                flags: PhonemeFlags::classify(p).bits(),
+                pitch: Some(pitch),
+                ..Default::default()
+            }).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_subsets() {
+        let library = Library {
+            base_path: PathBuf::from("/bank"),
+            language: Some(Language {
+                default: String::from("ja"),
+                supported: vec![]
+            }),
+            files: vec![
+                file("C4/ka.wav", None, 60, &["sil", "k", "a", "sil"]),
+                file("G4/ba.wav", None, 67, &["sil", "b", "a", "sil"]),
+                file("C4/cat.wav", Some("en"), 60, &["sil", "k", "{", "t", "sil"])
+            ],
+            ..Default::default()
+        };
+
+        let japanese = library.subset(&FileFilter::Language(String::from("ja")));
+        assert_eq!(japanese.files.len(), 2);
+        assert_ne!(japanese.uuid, library.uuid);
+
+        let filter = FileFilter::All(vec![FileFilter::Pitch(55..=62), FileFilter::Phonemes(vec![String::from("k")])]);
+        assert_eq!(library.subset(&filter).files.len(), 2);
+
+        let voiced_plosives = FileFilter::AnyLabel(LabelFilter::flags(PhonemeFlags::PLOSIVE | PhonemeFlags::VOICED));
+        assert_eq!(library.subset(&voiced_plosives).files[0].path, PathBuf::from("G4/ba.wav"));
+        assert_eq!(library.subset(&FileFilter::Not(Box::new(voiced_plosives))).files.len(), 2);
+
+        let consonants = library.subset_labels(&LabelFilter::Flag(String::from(PhonemeFlags::CONSONANT)));
+        assert_eq!(consonants.iter_labels().map(|l| l.curr.as_str()).collect::<Vec<_>>(), vec!["k", "b", "k", "t"]);
+
+        let vowels = library.subset_labels(&LabelFilter::flags(PhonemeFlags::VOWEL));
+        assert_eq!(vowels.iter_labels().map(|l| l.curr.as_str()).collect::<Vec<_>>(), vec!["a", "a", "{"]);
+
+        let mut rebased = library.subset(&FileFilter::PathPrefix(PathBuf::from("C4")));
+        rebased.rebase("/bank/C4");
+        assert_eq!(rebased.files[1].path, PathBuf::from("cat.wav"));
+    }
+}
@@ -1,9 +1,9 @@
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 
-use crate::{library::Library, parser::textgrid::from_textgrid, utterance::FileDescriptor, tools::ipa::FromIPA, Singer};
+use crate::{library::Library, singer::Language, parser::textgrid::from_textgrid, utterance::{FileDescriptor, PhonemeFlags}, tools::{arpa::FromARPA, convert::ConversionReport, czampa::FromCZampa, dictionary::Lexicon, ipa::FromIPA, pitch::{infer_pitch_from_path, PitchConfig}, Phoneset}, Singer};
 use crate::parser::lab::from_lab;
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -11,6 +11,7 @@ pub enum SourcePhoneset {
     Arpabet,
     IPA,
     XSampa,
+    CZampa,
     #[default]
     None
 }
@@ -20,15 +21,27 @@ impl SourcePhoneset {
             SourcePhoneset::Arpabet => "arpabet".to_string(),
             SourcePhoneset::IPA => "ipa".to_string(),
             SourcePhoneset::XSampa => "xsampa".to_string(),
+            SourcePhoneset::CZampa => "czampa".to_string(),
             SourcePhoneset::None => "none".to_string()
         }
     }
 
+    // Phoneset the source labels are written in, if they are not already X-SAMPA
+    pub fn phoneset(&self) -> Option<Phoneset> {
+        match self {
+            SourcePhoneset::Arpabet => Some(Phoneset::Arpa),
+            SourcePhoneset::IPA => Some(Phoneset::IPA),
+            SourcePhoneset::CZampa => Some(Phoneset::CZampa),
+            SourcePhoneset::XSampa | SourcePhoneset::None => None
+        }
+    }
+
     pub fn from_string(s: &str) -> Self {
         match s {
             "arpabet" => SourcePhoneset::Arpabet,
             "ipa" => SourcePhoneset::IPA,
             "xsampa" => SourcePhoneset::XSampa,
+            "czampa" => SourcePhoneset::CZampa,
             "none" => SourcePhoneset::None,
             _ => panic!("Invalid phoneset")
         }
@@ -70,26 +83,64 @@ pub struct GeneratorConfig {
     pub name: String,
     pub phoneset: SourcePhoneset,
     pub data_type: SourceDataType,
-    pub path: PathBuf
+    pub path: PathBuf,
+
+    #[serde(default)]
+    pub pitch: PitchConfig,
+
+    // Older configs set this at the top level; it overrides `pitch.discard_unpitched` when present
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discard_unpitched: Option<bool>,
+
+    // Pronunciation dictionary in the source phoneset, used to fill the files' aliases
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<PathBuf>
 }
 
 impl GeneratorConfig {
-    pub fn build(&self) -> Result<Singer> {
+    // The pitch settings the generator runs with, recorded in `Origin::pitch_analysis`. The algorithm is the one
+    // that actually runs, which differs from the requested one without WORLD.
+    pub fn pitch_config(&self) -> Result<PitchConfig> {
+        let mut pitch_config = self.pitch;
+        pitch_config.algorithm = pitch_config.algorithm.effective()
+            .ok_or(anyhow!("Pitch algorithm {:?} is not available in this build", self.pitch.algorithm))?;
+
+        if let Some(discard_unpitched) = self.discard_unpitched {
+            pitch_config.discard_unpitched = discard_unpitched;
+        }
+
+        Ok(pitch_config)
+    }
+
+    // Also returns what the phoneset conversion could not map, which is empty for X-SAMPA sources
+    pub fn build(&self) -> Result<(Singer, ConversionReport)> {
         let mut singer = Singer::new();
         singer.meta.name = self.name.clone();
 
+        let pitch_config = self.pitch_config()?;
+        singer.origin.pitch_analysis = Some(pitch_config);
+
         let mut lib = Library::default();
         lib.name = "Default".to_string();
         lib.is_default = true;
+        lib.language = Some(Language {
+            default: self.language.clone(),
+            supported: vec![self.language.clone()]
+        });
+
+        // Labels stay in the source phoneset until they are converted below
+        if let Some(phoneset) = self.phoneset.phoneset() {
+            lib.phoneset = phoneset;
+        }
 
         // Get all files in directory
         let files = std::fs::read_dir(&self.path).unwrap();
 
-        // Filter out non-wav files
+        // Filter out non-audio files
         let files = files.filter(|f| {
             let file = f.as_ref().unwrap().path();
             if let Some(ext) = file.extension() {
-                ext.eq("wav")
+                ext.eq("wav") || ext.eq("flac")
             } else {
                 false
             }
@@ -106,13 +157,14 @@ impl GeneratorConfig {
             let data_type = self.data_type.clone();
             pool.execute(move || {
                 let file = match data_type {
-                    SourceDataType::TextGrid => from_textgrid(&file, None),
+                    SourceDataType::TextGrid => from_textgrid(&file, None, &pitch_config),
                     SourceDataType::OtoIni => {
                         unimplemented!()
                     },
-                    SourceDataType::Label => from_lab(&file, None),
+                    SourceDataType::Label => from_lab(&file, None, &pitch_config),
                     SourceDataType::Empty => {
                         Ok(FileDescriptor {
+                            pitch: infer_pitch_from_path(&file),
                             path: file,
                             ..Default::default()
                         })
@@ -141,12 +193,32 @@ impl GeneratorConfig {
         singer.libraries.push(lib);
 
         // Convert phonemes if necessary
-        match self.phoneset {
-            SourcePhoneset::IPA => singer.from_ipa(),
-            _ => {}
+        let report = match self.phoneset {
+            SourcePhoneset::IPA => singer.from_ipa()?,
+            SourcePhoneset::Arpabet => singer.from_arpa()?,
+            SourcePhoneset::CZampa => singer.from_czampa()?,
+            _ => ConversionReport::default()
+        };
+
+        // Classify phonemes now that every label is in X-SAMPA
+        for library in singer.libraries.iter_mut() {
+            for file in library.files.iter_mut() {
+                file.labels.iter_mut().for_each(|l| l.classify());
+            }
         }
+        singer.flag_fields = Some(PhonemeFlags::field_names());
+
+        // Fill aliases from the pronunciation dictionary
+        if let Some(path) = &self.dictionary {
+            let mut lexicon = Lexicon::default();
+            lexicon.load(&self.language, path, self.phoneset.phoneset().unwrap_or_default())?;
 
-        Ok(singer)
+            for library in singer.libraries.iter_mut() {
+                lexicon.fill_aliases(library)?;
+            }
+        }
+
+        Ok((singer, report))
     }
 }
 
@@ -156,6 +228,15 @@ mod tests {
 
     use dotenv::dotenv;
 
+    #[test]
+    fn test_legacy_discard_unpitched() {
+        let json = r#"{"language": "ja", "name": "Test", "phoneset": "XSampa", "data_type": "Label", "path": "bank", "discard_unpitched": true}"#;
+        let cfg: GeneratorConfig = serde_json::from_str(json).unwrap();
+
+        assert!(!cfg.pitch.discard_unpitched);
+        assert!(cfg.pitch_config().unwrap().discard_unpitched);
+    }
+
     #[test]
     fn test_from_nest() {
         dotenv().ok();
@@ -165,10 +246,13 @@ mod tests {
             phoneset: SourcePhoneset::IPA,
             data_type: SourceDataType::TextGrid,
             name: String::from("Test Dataset"),
-            language: String::from("en")
+            language: String::from("en"),
+            pitch: PitchConfig::default(),
+            discard_unpitched: None,
+            dictionary: None
         };
 
-        let singer = cfg.build().unwrap();
+        let (singer, _) = cfg.build().unwrap();
 
         let path = cfg.path.join("singer.json");
         singer.save(&path).unwrap();
@@ -183,10 +267,13 @@ mod tests {
             phoneset: SourcePhoneset::Arpabet,
             data_type: SourceDataType::Label,
             name: String::from("Test Dataset"),
-            language: String::from("en")
+            language: String::from("en"),
+            pitch: PitchConfig::default(),
+            discard_unpitched: None,
+            dictionary: None
         };
 
-        let singer = cfg.build().unwrap();
+        let (singer, _) = cfg.build().unwrap();
 
         let path = cfg.path.join("singer.json");
         singer.save(&path).unwrap();
@@ -0,0 +1,195 @@
+// Lookup tables from phoneme context to the labels that have it. Each bucket is kept sorted by pitch,
+// so context lookups are constant time and pitch ranges are found with a binary search.
+
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use crate::{library::Library, utterance::{FileDescriptor, Utterance}, Singer};
+
+#[derive(Debug, Clone, Copy)]
+pub struct IndexEntry<'a> {
+    pub library: &'a Library,
+    pub file: &'a FileDescriptor,
+    pub utterance: &'a Utterance
+}
+
+impl<'a> IndexEntry<'a> {
+    // The label's own language, falling back to its file's and then its library's
+    pub fn language(&self) -> Option<&'a str> {
+        self.utterance.language.as_deref()
+            .or(self.file.language.as_deref())
+            .or(self.library.language.as_ref().map(|l| l.default.as_str()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context<'q> {
+    Triphone(&'q str, &'q str, &'q str),
+    // curr + next
+    Diphone(&'q str, &'q str),
+    Monophone(&'q str)
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct IndexFilter {
+    // Inclusive MIDI range; labels without a pitch never match
+    pub pitch: Option<RangeInclusive<u8>>,
+    pub language: Option<String>
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct UtteranceIndex<'a> {
+    entries: Vec<IndexEntry<'a>>,
+    triphones: HashMap<(&'a str, &'a str, &'a str), Vec<usize>>,
+    diphones: HashMap<(&'a str, &'a str), Vec<usize>>,
+    monophones: HashMap<&'a str, Vec<usize>>
+}
+
+impl<'a> UtteranceIndex<'a> {
+    pub fn from_library(library: &'a Library) -> Self {
+        Self::build([library])
+    }
+
+    pub fn from_singer(singer: &'a Singer) -> Self {
+        Self::build(singer.libraries.iter())
+    }
+
+    fn build<I: IntoIterator<Item = &'a Library>>(libraries: I) -> Self {
+        let mut index = UtteranceIndex::default();
+
+        for library in libraries {
+            for file in library.files.iter() {
+                for utterance in file.labels.iter() {
+                    let i = index.entries.len();
+                    index.entries.push(IndexEntry { library, file, utterance });
+
+                    index.triphones.entry((&utterance.prev, &utterance.curr, &utterance.next)).or_default().push(i);
+                    index.diphones.entry((&utterance.curr, &utterance.next)).or_default().push(i);
+                    index.monophones.entry(&utterance.curr).or_default().push(i);
+                }
+            }
+        }
+
+        let entries = &index.entries;
+        let buckets = index.triphones.values_mut()
+            .chain(index.diphones.values_mut())
+            .chain(index.monophones.values_mut());
+        for bucket in buckets {
+            bucket.sort_by_key(|i| entries[*i].utterance.pitch);
+        }
+
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Every distinct `curr` symbol in the index
+    pub fn phonemes(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.monophones.keys().copied()
+    }
+
+    pub fn triphone(&self, prev: &str, curr: &str, next: &str) -> Vec<IndexEntry<'a>> {
+        self.query(Context::Triphone(prev, curr, next), &IndexFilter::default())
+    }
+
+    pub fn diphone(&self, curr: &str, next: &str) -> Vec<IndexEntry<'a>> {
+        self.query(Context::Diphone(curr, next), &IndexFilter::default())
+    }
+
+    pub fn monophone(&self, curr: &str) -> Vec<IndexEntry<'a>> {
+        self.query(Context::Monophone(curr), &IndexFilter::default())
+    }
+
+    pub fn query(&self, context: Context, filter: &IndexFilter) -> Vec<IndexEntry<'a>> {
+        let bucket = match context {
+            Context::Triphone(prev, curr, next) => self.triphones.get(&(prev, curr, next)),
+            Context::Diphone(curr, next) => self.diphones.get(&(curr, next)),
+            Context::Monophone(curr) => self.monophones.get(curr)
+        };
+
+        let Some(bucket) = bucket else {
+            return vec![];
+        };
+
+        let bucket = match &filter.pitch {
+            Some(range) => {
+                let pitch = |i: &usize| self.entries[*i].utterance.pitch;
+                let start = bucket.partition_point(|i| pitch(i) < Some(*range.start()));
+                let end = bucket.partition_point(|i| pitch(i) <= Some(*range.end()));
+
+                &bucket[start..end.max(start)]
+            },
+            None => &bucket[..]
+        };
+
+        bucket.iter()
+            .map(|i| self.entries[*i])
+            .filter(|e| filter.language.as_ref().is_none_or(|l| e.language() == Some(l.as_str())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(prev: &str, curr: &str, next: &str, pitch: Option<u8>) -> Utterance {
+        Utterance {
+            prev: prev.to_string(),
+            curr: curr.to_string(),
+            next: next.to_string(),
+            pitch,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_index_queries() {
+        let library = Library {
+            files: vec![
+                FileDescriptor {
+                    language: Some(String::from("ja")),
+                    labels: vec![label("sil", "k", "a", None), label("k", "a", "sil", Some(64))],
+                    ..Default::default()
+                },
+                FileDescriptor {
+                    language: Some(String::from("en")),
+                    labels: vec![label("sil", "k", "a", None), label("k", "a", "t", Some(57)), label("a", "t", "sil", None)],
+                    ..Default::default()
+                }
+            ],
+            ..Default::default()
+        };
+
+        let index = UtteranceIndex::from_library(&library);
+        assert_eq!(index.len(), 5);
+
+        assert_eq!(index.triphone("k", "a", "t").len(), 1);
+        assert_eq!(index.diphone("k", "a").len(), 2);
+        assert_eq!(index.monophone("a").len(), 2);
+        assert!(index.monophone("o").is_empty());
+
+        // Buckets are sorted by pitch
+        let a = index.monophone("a");
+        assert_eq!(a.iter().map(|e| e.utterance.pitch).collect::<Vec<_>>(), vec![Some(57), Some(64)]);
+
+        let filter = IndexFilter {
+            pitch: Some(60..=70),
+            ..Default::default()
+        };
+        let high = index.query(Context::Monophone("a"), &filter);
+        assert_eq!(high.len(), 1);
+        assert_eq!(high[0].utterance.next, "sil");
+
+        let filter = IndexFilter {
+            language: Some(String::from("en")),
+            ..Default::default()
+        };
+        assert_eq!(index.query(Context::Diphone("k", "a"), &filter).len(), 1);
+    }
+}
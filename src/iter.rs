@@ -0,0 +1,265 @@
+// Borrowing and owning iteration over libraries and singers. Libraries iterate over their files and singers over
+// their libraries; the label iterators walk every file in order.
+
+use std::{slice, vec};
+
+use crate::{library::Library, utterance::{FileDescriptor, Utterance}, Singer};
+
+// A label with the labels around it in the same file
+#[derive(Debug, Clone, Copy)]
+pub struct LabelContext<'a> {
+    pub file: &'a FileDescriptor,
+    pub prev: Option<&'a Utterance>,
+    pub label: &'a Utterance,
+    pub next: Option<&'a Utterance>
+}
+
+fn in_context(file: &FileDescriptor) -> impl Iterator<Item = LabelContext<'_>> {
+    file.labels.iter().enumerate().map(move |(i, label)| LabelContext {
+        file,
+        prev: i.checked_sub(1).map(|j| &file.labels[j]),
+        label,
+        next: file.labels.get(i + 1)
+    })
+}
+
+impl Library {
+    pub fn iter(&self) -> slice::Iter<'_, FileDescriptor> {
+        self.files.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, FileDescriptor> {
+        self.files.iter_mut()
+    }
+
+    pub fn iter_labels(&self) -> impl Iterator<Item = &Utterance> {
+        self.files.iter().flat_map(|f| f.labels.iter())
+    }
+
+    pub fn iter_labels_mut(&mut self) -> impl Iterator<Item = &mut Utterance> {
+        self.files.iter_mut().flat_map(|f| f.labels.iter_mut())
+    }
+
+    pub fn labels_with_files(&self) -> impl Iterator<Item = (&FileDescriptor, &Utterance)> {
+        self.files.iter().flat_map(|f| f.labels.iter().map(move |l| (f, l)))
+    }
+
+    pub fn labels_in_context(&self) -> impl Iterator<Item = LabelContext<'_>> {
+        self.files.iter().flat_map(in_context)
+    }
+}
+
+impl IntoIterator for Library {
+    type Item = FileDescriptor;
+    type IntoIter = vec::IntoIter<FileDescriptor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Library {
+    type Item = &'a FileDescriptor;
+    type IntoIter = slice::Iter<'a, FileDescriptor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Library {
+    type Item = &'a mut FileDescriptor;
+    type IntoIter = slice::IterMut<'a, FileDescriptor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.iter_mut()
+    }
+}
+
+impl Singer {
+    pub fn iter(&self) -> slice::Iter<'_, Library> {
+        self.libraries.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Library> {
+        self.libraries.iter_mut()
+    }
+
+    // Every file with the library it belongs to
+    pub fn files(&self) -> impl Iterator<Item = (&Library, &FileDescriptor)> {
+        self.libraries.iter().flat_map(|l| l.files.iter().map(move |f| (l, f)))
+    }
+
+    pub fn files_mut(&mut self) -> impl Iterator<Item = &mut FileDescriptor> {
+        self.libraries.iter_mut().flat_map(|l| l.files.iter_mut())
+    }
+
+    pub fn iter_labels(&self) -> impl Iterator<Item = &Utterance> {
+        self.libraries.iter().flat_map(|l| l.iter_labels())
+    }
+
+    pub fn iter_labels_mut(&mut self) -> impl Iterator<Item = &mut Utterance> {
+        self.libraries.iter_mut().flat_map(|l| l.iter_labels_mut())
+    }
+
+    pub fn labels_with_files(&self) -> impl Iterator<Item = (&FileDescriptor, &Utterance)> {
+        self.libraries.iter().flat_map(|l| l.labels_with_files())
+    }
+
+    pub fn labels_in_context(&self) -> impl Iterator<Item = LabelContext<'_>> {
+        self.libraries.iter().flat_map(|l| l.labels_in_context())
+    }
+}
+
+impl IntoIterator for Singer {
+    type Item = Library;
+    type IntoIter = vec::IntoIter<Library>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.libraries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Singer {
+    type Item = &'a Library;
+    type IntoIter = slice::Iter<'a, Library>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.libraries.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Singer {
+    type Item = &'a mut Library;
+    type IntoIter = slice::IterMut<'a, Library>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.libraries.iter_mut()
+    }
+}
+
+// With `rayon::prelude::*` in scope, `par_iter`, `par_iter_mut` and `into_par_iter` work like their sequential
+// counterparts above
+#[cfg(feature = "parallel")]
+mod parallel {
+    use rayon::prelude::*;
+
+    use super::*;
+
+    impl Library {
+        pub fn par_iter_labels(&self) -> impl ParallelIterator<Item = &Utterance> {
+            self.files.par_iter().flat_map_iter(|f| f.labels.iter())
+        }
+
+        pub fn par_iter_labels_mut(&mut self) -> impl ParallelIterator<Item = &mut Utterance> {
+            self.files.par_iter_mut().flat_map_iter(|f| f.labels.iter_mut())
+        }
+
+        pub fn par_labels_in_context(&self) -> impl ParallelIterator<Item = LabelContext<'_>> {
+            self.files.par_iter().flat_map_iter(in_context)
+        }
+    }
+
+    impl IntoParallelIterator for Library {
+        type Item = FileDescriptor;
+        type Iter = rayon::vec::IntoIter<FileDescriptor>;
+
+        fn into_par_iter(self) -> Self::Iter {
+            self.files.into_par_iter()
+        }
+    }
+
+    impl<'a> IntoParallelIterator for &'a Library {
+        type Item = &'a FileDescriptor;
+        type Iter = rayon::slice::Iter<'a, FileDescriptor>;
+
+        fn into_par_iter(self) -> Self::Iter {
+            self.files.par_iter()
+        }
+    }
+
+    impl<'a> IntoParallelIterator for &'a mut Library {
+        type Item = &'a mut FileDescriptor;
+        type Iter = rayon::slice::IterMut<'a, FileDescriptor>;
+
+        fn into_par_iter(self) -> Self::Iter {
+            self.files.par_iter_mut()
+        }
+    }
+
+    impl Singer {
+        // Files of all libraries, split across threads regardless of which library they are in
+        pub fn par_files(&self) -> impl ParallelIterator<Item = (&Library, &FileDescriptor)> {
+            self.libraries.par_iter().flat_map(|l| l.files.par_iter().map(move |f| (l, f)))
+        }
+
+        pub fn par_files_mut(&mut self) -> impl ParallelIterator<Item = &mut FileDescriptor> {
+            self.libraries.par_iter_mut().flat_map(|l| l.files.par_iter_mut())
+        }
+
+        pub fn par_iter_labels(&self) -> impl ParallelIterator<Item = &Utterance> {
+            self.par_files().flat_map_iter(|(_, f)| f.labels.iter())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(phonemes: &[&str]) -> FileDescriptor {
+        FileDescriptor {
+            labels: phonemes.iter().map(|p| Utterance {
+                curr: p.to_string(),
+                ..Default::default()
+            }).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_iteration_keeps_files() {
+        let mut singer = Singer {
+            libraries: vec![Library {
+                files: vec![file(&["k", "a"]), file(&["s", "a"])],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let library = &singer.libraries[0];
+        assert_eq!(library.iter().count(), 2);
+        assert_eq!(library.into_iter().count(), 2);
+        assert_eq!(library.files.len(), 2);
+
+        let contexts = singer.labels_in_context().collect::<Vec<_>>();
+        assert_eq!(contexts.len(), 4);
+        assert!(contexts[0].prev.is_none());
+        assert_eq!(contexts[0].next.unwrap().curr, "a");
+        assert_eq!(contexts[3].prev.unwrap().curr, "s");
+        assert!(std::ptr::eq(contexts[3].file, &singer.libraries[0].files[1]));
+
+        for label in singer.iter_labels_mut() {
+            label.pitch = Some(60);
+        }
+        assert!(singer.labels_with_files().all(|(_, l)| l.pitch == Some(60)));
+
+        let files = singer.into_iter().flat_map(|l| l.into_iter()).count();
+        assert_eq!(files, 2);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_iteration() {
+        use rayon::prelude::*;
+
+        let mut library = Library {
+            files: vec![file(&["k", "a"]), file(&["s", "a"])],
+            ..Default::default()
+        };
+
+        library.par_iter_mut().for_each(|f| f.pitch = Some(60));
+        assert!(library.par_iter().all(|f| f.pitch == Some(60)));
+        assert_eq!(library.par_iter_labels().filter(|l| l.curr == "a").count(), 2);
+    }
+}
@@ -4,13 +4,20 @@ pub mod encode;
 pub mod time;
 pub mod tools;
 pub mod library;
+pub mod index;
+pub mod features;
+pub mod merge;
+pub mod diff;
+pub mod filter;
+pub mod iter;
+pub mod select;
 
 pub mod prelude;
 
-#[cfg(feature = "generator")]
+#[cfg(feature = "generator-core")]
 pub mod parser;
 
-#[cfg(feature = "generator")]
+#[cfg(feature = "generator-core")]
 pub mod generate;
 
 pub use singer::Singer;
@@ -1,9 +1,10 @@
 use std::path::PathBuf;
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{singer::Language, time::Timestamp, utterance::{FileDescriptor, Utterance}};
+use crate::{index::UtteranceIndex, singer::Language, time::Timestamp, tools::{coverage::Coverage, Phoneset}, utterance::FileDescriptor};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Library {
@@ -12,6 +13,10 @@ pub struct Library {
     pub base_path: PathBuf,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub language: Option<Language>,
+    // Phoneset the labels are written in. JSON files written before this was recorded are read as X-SAMPA;
+    // bincode has no defaults, so older `.bin` singers no longer decode and have to be re-saved from JSON.
+    #[serde(default)]
+    pub phoneset: Phoneset,
     pub is_default: bool,
     pub files: Vec<FileDescriptor>,
 
@@ -22,15 +27,27 @@ pub struct Library {
 }
 
 impl Library {
-    pub fn iter_labels(&self) -> impl Iterator<Item = &Utterance> {
-        self.files.iter().flat_map(|f| f.labels.iter())
+    pub fn index(&self) -> UtteranceIndex<'_> {
+        UtteranceIndex::from_library(self)
+    }
+
+    pub fn coverage(&self) -> Coverage {
+        Coverage::from_library(self)
     }
-}
 
-impl Iterator for Library {
-    type Item = FileDescriptor;
+    pub fn validate_areas(&self) -> Result<()> {
+        for label in self.iter_labels() {
+            label.validate_areas()?;
+        }
+
+        Ok(())
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.files.pop()
+    // Finds the file whose pitch is closest to the requested MIDI note. Files without a pitch are ignored.
+    pub fn nearest_pitch(&self, midi: u8) -> Option<&FileDescriptor> {
+        self.files.iter()
+            .filter_map(|f| f.pitch.map(|p| (p.abs_diff(midi), f)))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, f)| f)
     }
 }
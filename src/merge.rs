@@ -0,0 +1,326 @@
+// Combining banks that were generated in separate sessions. Files are matched by their path after rebasing onto
+// the target library's `base_path`; libraries are matched by name. Which version is newer is decided by the
+// singers' `Origin::creation_date`, as the audio of a duplicate is the same file in both.
+
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{library::Library, utterance::FileDescriptor, Singer};
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicateStrategy {
+    // The file or alias already in the target wins
+    #[default]
+    KeepFirst,
+    // The file or alias from the singer created last wins. Libraries carry no date, so on their own the incoming
+    // one is taken as newer.
+    KeepNewest,
+    // Both are kept and the incoming aliases get a numbered suffix (`ka` -> `ka_2`). Files with the same path are
+    // one recording, so the incoming aliases and labels are merged into the existing file instead.
+    KeepBoth
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    pub added: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+    // Files merged into an existing one with the same path
+    pub merged: usize,
+    // Aliases renamed or dropped to resolve a collision, as (old, new); None if dropped
+    pub aliases: Vec<(String, Option<String>)>,
+    // Libraries that were given a fresh Uuid because theirs was already taken
+    pub new_uuids: Vec<String>
+}
+
+impl Library {
+    pub fn merge(&mut self, other: Library, strategy: DuplicateStrategy) -> MergeReport {
+        self.merge_files(other, strategy, true)
+    }
+
+    fn merge_files(&mut self, other: Library, strategy: DuplicateStrategy, incoming_is_newer: bool) -> MergeReport {
+        let strategy = match strategy {
+            DuplicateStrategy::KeepNewest if !incoming_is_newer => DuplicateStrategy::KeepFirst,
+            strategy => strategy
+        };
+
+        let mut report = MergeReport::default();
+        let other_base = other.base_path.clone();
+
+        for mut file in other.files {
+            file.path = rebase(&file.path, &other_base, &self.base_path);
+
+            let mut replace_at = None;
+            if let Some(i) = self.files.iter().position(|f| f.path == file.path) {
+                match strategy {
+                    DuplicateStrategy::KeepFirst => {
+                        report.skipped += 1;
+                        continue;
+                    },
+                    DuplicateStrategy::KeepNewest => {
+                        self.files.remove(i);
+                        replace_at = Some(i);
+                        report.replaced += 1;
+                    },
+                    DuplicateStrategy::KeepBoth => {
+                        file.aliases.retain(|a| !self.files[i].aliases.contains(a));
+                        self.resolve_aliases(&mut file, strategy, &mut report);
+
+                        let existing = &mut self.files[i];
+                        existing.aliases.extend(file.aliases);
+                        for label in file.labels {
+                            if !existing.labels.contains(&label) {
+                                existing.labels.push(label);
+                            }
+                        }
+                        existing.labels.sort_by_key(|l| l.start.value);
+
+                        report.merged += 1;
+                        continue;
+                    }
+                }
+            }
+
+            self.resolve_aliases(&mut file, strategy, &mut report);
+
+            // A replaced file hands its place in the list to its replacement
+            match replace_at {
+                Some(i) => self.files.insert(i, file),
+                None => {
+                    self.files.push(file);
+                    report.added += 1;
+                }
+            }
+        }
+
+        report
+    }
+
+    fn resolve_aliases(&mut self, file: &mut FileDescriptor, strategy: DuplicateStrategy, report: &mut MergeReport) {
+        let mut aliases = vec![];
+
+        for alias in std::mem::take(&mut file.aliases) {
+            let taken = self.files.iter().any(|f| f.aliases.contains(&alias));
+            if !taken {
+                aliases.push(alias);
+                continue;
+            }
+
+            match strategy {
+                DuplicateStrategy::KeepFirst => report.aliases.push((alias, None)),
+                DuplicateStrategy::KeepNewest => {
+                    for f in self.files.iter_mut() {
+                        f.aliases.retain(|a| *a != alias);
+                    }
+
+                    aliases.push(alias);
+                },
+                DuplicateStrategy::KeepBoth => {
+                    let used = self.files.iter().flat_map(|f| f.aliases.iter()).chain(aliases.iter()).cloned().collect::<HashSet<String>>();
+                    let renamed = (2..).map(|n| format!("{}_{}", alias, n)).find(|a| !used.contains(a)).unwrap();
+
+                    report.aliases.push((alias, Some(renamed.clone())));
+                    aliases.push(renamed);
+                }
+            }
+        }
+
+        file.aliases = aliases;
+    }
+}
+
+impl Singer {
+    // Libraries with the same name are merged, others are added. Metadata of `self` is kept.
+    pub fn merge(&mut self, other: Singer, strategy: DuplicateStrategy) -> MergeReport {
+        let mut report = MergeReport::default();
+
+        // RFC 3339 dates in the same offset, as `Origin::now` writes them, sort chronologically
+        let (existing_date, incoming_date) = (&self.origin.creation_date, &other.origin.creation_date);
+        let incoming_is_newer = existing_date.is_empty() || incoming_date.is_empty() || incoming_date >= existing_date;
+
+        for language in other.language.supported {
+            if !self.language.supported.contains(&language) {
+                self.language.supported.push(language);
+            }
+        }
+
+        for mut library in other.libraries {
+            if let Some(existing) = self.libraries.iter_mut().find(|l| l.name == library.name) {
+                let merged = existing.merge_files(library, strategy, incoming_is_newer);
+
+                report.added += merged.added;
+                report.replaced += merged.replaced;
+                report.skipped += merged.skipped;
+                report.merged += merged.merged;
+                report.aliases.extend(merged.aliases);
+                continue;
+            }
+
+            // Generated libraries all start out with the nil Uuid, so collisions are common
+            if library.uuid.is_nil() || self.libraries.iter().any(|l| l.uuid == library.uuid) {
+                library.uuid = Uuid::new_v4();
+                report.new_uuids.push(library.name.clone());
+            }
+
+            if self.libraries.iter().any(|l| l.is_default) {
+                library.is_default = false;
+            }
+
+            report.added += library.files.len();
+            self.libraries.push(library);
+        }
+
+        self.flag_fields = self.flag_fields.take().or(other.flag_fields);
+        self.extra_fields = self.extra_fields.take().or(other.extra_fields);
+
+        report
+    }
+}
+
+// Re-expresses a path relative to `from` as relative to `to`. Paths outside `to` become absolute.
+pub fn rebase(path: &Path, from: &Path, to: &Path) -> PathBuf {
+    if from == to || path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    let absolute = from.join(path);
+    absolute.strip_prefix(to).map(|p| p.to_path_buf()).unwrap_or(absolute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{singer::Origin, time::Timestamp, utterance::Utterance};
+
+    fn file(path: &str, alias: &str) -> FileDescriptor {
+        FileDescriptor {
+            path: PathBuf::from(path),
+            aliases: vec![alias.to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_libraries() {
+        let first = Library {
+            name: String::from("Default"),
+            base_path: PathBuf::from("/bank"),
+            files: vec![file("a/ka.wav", "ka"), file("a/sa.wav", "sa")],
+            ..Default::default()
+        };
+        let second = Library {
+            name: String::from("Default"),
+            base_path: PathBuf::from("/bank/a"),
+            files: vec![file("ka.wav", "ka"), file("ta.wav", "sa")],
+            ..Default::default()
+        };
+
+        let mut library = first.clone();
+        let report = library.merge(second.clone(), DuplicateStrategy::KeepFirst);
+        assert_eq!((report.added, report.skipped), (1, 1));
+        assert_eq!(library.files[2].path, PathBuf::from("a/ta.wav"));
+        assert!(library.files[2].aliases.is_empty());
+
+        let mut library = first.clone();
+        let report = library.merge(second.clone(), DuplicateStrategy::KeepBoth);
+        assert_eq!((report.added, report.merged), (1, 1));
+        assert_eq!(library.files.len(), 3);
+        assert_eq!(library.files[0].aliases, vec!["ka"]);
+        assert_eq!(library.files[2].aliases, vec!["sa_2"]);
+
+        let mut library = first;
+        let report = library.merge(second, DuplicateStrategy::KeepNewest);
+        assert_eq!((report.added, report.replaced), (1, 1));
+        assert_eq!(library.files[0].aliases, vec!["ka"]);
+        assert!(library.files[1].aliases.is_empty());
+        assert_eq!(library.files[2].aliases, vec!["sa"]);
+
+        assert_eq!(rebase(Path::new("ka.wav"), Path::new("/other"), Path::new("/bank")), PathBuf::from("/other/ka.wav"));
+    }
+
+    #[test]
+    fn test_keep_both_same_path() {
+        let label = |curr: &str, start: i64| Utterance {
+            curr: curr.to_string(),
+            start: Timestamp::new(start),
+            end: Timestamp::new(start + 100_000),
+            ..Default::default()
+        };
+
+        let mut first = file("ka.wav", "ka");
+        first.labels = vec![label("k", 0), label("a", 100_000)];
+        let mut second = file("ka.wav", "ka");
+        second.aliases.push(String::from("sa"));
+        second.labels = vec![label("a", 100_000), label("sil", 200_000)];
+
+        let mut library = Library {
+            files: vec![first, file("sa.wav", "sa")],
+            ..Default::default()
+        };
+        let report = library.merge(Library { files: vec![second], ..Default::default() }, DuplicateStrategy::KeepBoth);
+
+        // One file per path, so lookups by path still see everything
+        assert_eq!((report.added, report.merged), (0, 1));
+        assert_eq!(library.files.len(), 2);
+        assert_eq!(library.files[0].aliases, vec!["ka", "sa_2"]);
+        assert_eq!(library.files[0].labels.iter().map(|l| l.curr.as_str()).collect::<Vec<_>>(), vec!["k", "a", "sil"]);
+    }
+
+    #[test]
+    fn test_merge_singers() {
+        let library = |name: &str| Library {
+            name: name.to_string(),
+            is_default: true,
+            files: vec![file("ka.wav", "ka")],
+            ..Default::default()
+        };
+
+        let mut singer = Singer {
+            libraries: vec![library("Normal")],
+            ..Default::default()
+        };
+        let other = Singer {
+            libraries: vec![library("Normal"), library("Whisper")],
+            ..Default::default()
+        };
+
+        let report = singer.merge(other, DuplicateStrategy::KeepFirst);
+        assert_eq!(singer.libraries.len(), 2);
+        assert_eq!(report.new_uuids, vec!["Whisper"]);
+        assert!(!singer.libraries[1].uuid.is_nil());
+        assert!(!singer.libraries[1].is_default);
+    }
+
+    #[test]
+    fn test_merge_keeps_newest_singer() {
+        let singer = |date: &str, alias: &str| Singer {
+            origin: Origin {
+                creation_date: date.to_string(),
+                ..Default::default()
+            },
+            libraries: vec![Library {
+                name: String::from("Normal"),
+                files: vec![file("ka.wav", alias)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let old = singer("2024-03-01T10:00:00+00:00", "ka");
+        let new = singer("2024-05-01T10:00:00+00:00", "ka_new");
+
+        let mut merged = old.clone();
+        let report = merged.merge(new.clone(), DuplicateStrategy::KeepNewest);
+        assert_eq!((report.replaced, report.skipped), (1, 0));
+        assert_eq!(merged.libraries[0].files[0].aliases, vec!["ka_new"]);
+
+        // Merging the older session into the newer one keeps what is there
+        let mut merged = new;
+        let report = merged.merge(old, DuplicateStrategy::KeepNewest);
+        assert_eq!((report.replaced, report.skipped), (0, 1));
+        assert_eq!(merged.libraries[0].files[0].aliases, vec!["ka_new"]);
+    }
+}
@@ -3,11 +3,11 @@ use std::path::Path;
 use textgridde_rs::textgrid::{TextGrid, Tier};
 use anyhow::{bail, Result};
 
-use crate::time::Timestamp;
-use crate::tools::pitch::write_pitch;
+use crate::time::{TimeRange, Timestamp};
+use crate::tools::pitch::{file_pitch, write_pitch, PitchConfig};
 use crate::utterance::{FileDescriptor, Utterance};
 
-pub fn from_lab<P: AsRef<Path>>(audio_path: P, lab_path: Option<P>) -> Result<FileDescriptor> {
+pub fn from_lab<P: AsRef<Path>>(audio_path: P, lab_path: Option<P>, pitch_config: &PitchConfig) -> Result<FileDescriptor> {
     let lab_path = if let Some(p) = lab_path {
         p.as_ref().to_path_buf()
     } else {
@@ -38,8 +38,8 @@ pub fn from_lab<P: AsRef<Path>>(audio_path: P, lab_path: Option<P>) -> Result<Fi
             continue;
         }
 
-        let start = start.unwrap().parse::<u64>()?;
-        let end = end.unwrap().parse::<u64>()?;
+        let start = start.unwrap().parse::<i64>()?;
+        let end = end.unwrap().parse::<i64>()?;
         let label = label.unwrap();
 
         let label = match label {
@@ -59,9 +59,9 @@ pub fn from_lab<P: AsRef<Path>>(audio_path: P, lab_path: Option<P>) -> Result<Fi
         let mut utterance = Utterance::default();
         
         // Parse timestamps
-        utterance.start = Timestamp::new(start / 10);
-        utterance.end = Timestamp::new(end / 10);
-        utterance.midpoint = (utterance.end - utterance.start) / 2.0 + utterance.start;
+        let range = TimeRange::new(Timestamp::from_htk(*start), Timestamp::from_htk(*end));
+        utterance.set_range(range);
+        utterance.midpoint = range.midpoint();
 
         // Parse annotation data
         utterance.prev = previous.clone();
@@ -73,11 +73,8 @@ pub fn from_lab<P: AsRef<Path>>(audio_path: P, lab_path: Option<P>) -> Result<Fi
             String::from("sil")
         };
 
-        let flags = 0u8;
-
-        // Parse pitch and meta
+        // Pitch is filled by write_pitch below, flags once the labels are in X-SAMPA
         utterance.pitch = None;
-        utterance.flags = flags;
 
         utterances.push(utterance);
 
@@ -86,14 +83,14 @@ pub fn from_lab<P: AsRef<Path>>(audio_path: P, lab_path: Option<P>) -> Result<Fi
 
 
     // Fill the pitch fields
-    write_pitch(&audio_path, &mut utterances)?;
+    write_pitch(&audio_path, &mut utterances, pitch_config)?;
 
 
     // Create the file descriptor
     let file = FileDescriptor {
         path: audio_path.as_ref().to_path_buf(),
         aliases: vec![],
-        pitch: None,
+        pitch: file_pitch(&audio_path, &utterances),
         analysis_files: None,
         language: None,
         labels: utterances,
@@ -3,11 +3,11 @@ use std::path::Path;
 use textgridde_rs::textgrid::{TextGrid, Tier};
 use anyhow::{bail, Result};
 
-use crate::time::Timestamp;
-use crate::tools::pitch::write_pitch;
+use crate::time::{TimeRange, Timestamp};
+use crate::tools::pitch::{file_pitch, write_pitch, PitchConfig};
 use crate::utterance::{FileDescriptor, Utterance};
 
-pub fn from_textgrid<P: AsRef<Path>>(audio_path: P, textgrid_path: Option<P>) -> Result<FileDescriptor> {
+pub fn from_textgrid<P: AsRef<Path>>(audio_path: P, textgrid_path: Option<P>, pitch_config: &PitchConfig) -> Result<FileDescriptor> {
     let textgrid_path = if let Some(p) = textgrid_path {
         p.as_ref().to_path_buf()
     } else {
@@ -50,9 +50,9 @@ pub fn from_textgrid<P: AsRef<Path>>(audio_path: P, textgrid_path: Option<P>) ->
         let mut utterance = Utterance::default();
 
         // Parse timestamps
-        utterance.start = Timestamp::from_seconds(*interval.xmin());
-        utterance.end = Timestamp::from_seconds(*interval.xmax());
-        utterance.midpoint = (utterance.end - utterance.start) / 2.0 + utterance.start;
+        let range = TimeRange::new(Timestamp::from_seconds(*interval.xmin()), Timestamp::from_seconds(*interval.xmax()));
+        utterance.set_range(range);
+        utterance.midpoint = range.midpoint();
 
         // Parse annotation data
         utterance.prev = previous.clone();
@@ -63,11 +63,8 @@ pub fn from_textgrid<P: AsRef<Path>>(audio_path: P, textgrid_path: Option<P>) ->
             String::from("sil")
         };
 
-        let flags = 0u8;
-
-        // Parse pitch and meta
+        // Pitch is filled by write_pitch below, flags once the labels are in X-SAMPA
         utterance.pitch = None;
-        utterance.flags = flags;
 
         utterances.push(utterance);
 
@@ -76,14 +73,14 @@ pub fn from_textgrid<P: AsRef<Path>>(audio_path: P, textgrid_path: Option<P>) ->
 
 
     // Fill the pitch fields
-    write_pitch(&audio_path, &mut utterances)?;
+    write_pitch(&audio_path, &mut utterances, pitch_config)?;
 
 
     // Create the file descriptor
     let file = FileDescriptor {
         path: audio_path.as_ref().to_path_buf(),
         aliases: vec![],
-        pitch: None,
+        pitch: file_pitch(&audio_path, &utterances),
         analysis_files: None,
         language: None,
         labels: utterances,
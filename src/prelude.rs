@@ -4,9 +4,16 @@ pub use crate::encode::*;
 pub use crate::time::*;
 pub use crate::tools::*;
 pub use crate::library::*;
+pub use crate::index::*;
+pub use crate::features::*;
+pub use crate::merge::*;
+pub use crate::diff::*;
+pub use crate::filter::*;
+pub use crate::iter::*;
+pub use crate::select::*;
 
-#[cfg(feature = "generator")]
+#[cfg(feature = "generator-core")]
 pub use crate::parser::*;
 
-#[cfg(feature = "generator")]
+#[cfg(feature = "generator-core")]
 pub use crate::generate::*;
\ No newline at end of file
@@ -0,0 +1,321 @@
+// Unit selection over a library: picks one label per target phoneme, minimizing the sum of target costs
+// (context, pitch, duration) and concatenation costs between neighbours with a Viterbi search.
+
+use std::collections::BTreeSet;
+
+use crate::{features::phoneme_distance, index::{Context, IndexEntry, IndexFilter, UtteranceIndex}, library::Library, time::Timestamp, Singer};
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Target {
+    pub phoneme: String,
+    // MIDI note
+    pub pitch: Option<u8>,
+    pub duration: Option<Timestamp>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectionWeights {
+    // Per mismatched neighbour, and per substituted phoneme
+    pub context: f64,
+    // Per octave of distance between target and candidate pitch
+    pub pitch: f64,
+    // Per unit of |ln(candidate length / target duration)|
+    pub duration: f64,
+    // Per join between labels that are not adjacent in the same recording
+    pub concatenation: f64
+}
+
+impl Default for SelectionWeights {
+    fn default() -> Self {
+        SelectionWeights {
+            context: 1.0,
+            pitch: 1.0,
+            duration: 0.5,
+            concatenation: 0.5
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchLevel {
+    Triphone,
+    Diphone,
+    Monophone,
+    // A phoneme from the same similarity class
+    Similar
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectionConfig {
+    pub weights: SelectionWeights,
+    // Levels tried in order; candidates come from the first level that has any
+    pub fallback: Vec<MatchLevel>,
+    // Groups of phonemes that may stand in for each other
+    pub similar: Vec<Vec<String>>,
+    // Phonemes within this articulatory distance of the target may stand in for it as well (see `features`)
+    pub similar_distance: Option<f64>,
+    pub max_candidates: usize,
+    pub filter: IndexFilter
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        SelectionConfig {
+            weights: SelectionWeights::default(),
+            fallback: vec![MatchLevel::Triphone, MatchLevel::Diphone, MatchLevel::Monophone, MatchLevel::Similar],
+            similar: vec![],
+            similar_distance: None,
+            max_candidates: 50,
+            filter: IndexFilter::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Selection<'a> {
+    pub entry: IndexEntry<'a>,
+    pub level: MatchLevel,
+    // Target cost of this unit plus the concatenation cost of joining it to the previous one
+    pub cost: f64
+}
+
+pub struct UnitSelector<'a> {
+    pub index: UtteranceIndex<'a>,
+    pub config: SelectionConfig
+}
+
+struct Candidate<'a> {
+    entry: IndexEntry<'a>,
+    level: MatchLevel,
+    target_cost: f64
+}
+
+impl<'a> UnitSelector<'a> {
+    pub fn from_library(library: &'a Library, config: SelectionConfig) -> Self {
+        UnitSelector {
+            index: UtteranceIndex::from_library(library),
+            config
+        }
+    }
+
+    pub fn from_singer(singer: &'a Singer, config: SelectionConfig) -> Self {
+        UnitSelector {
+            index: UtteranceIndex::from_singer(singer),
+            config
+        }
+    }
+
+    // Returns one selection per target, or None where no level of the fallback found a candidate
+    pub fn select(&self, targets: &[Target]) -> Vec<Option<Selection<'a>>> {
+        let candidates = (0..targets.len()).map(|i| self.candidates(targets, i)).collect::<Vec<_>>();
+
+        // Viterbi: best accumulated cost and back pointer for every candidate
+        let mut costs: Vec<Vec<(f64, Option<usize>)>> = Vec::with_capacity(targets.len());
+        for (i, position) in candidates.iter().enumerate() {
+            let previous = if i > 0 { Some((&candidates[i - 1], &costs[i - 1])) } else { None };
+
+            let row = position.iter().map(|c| {
+                match previous {
+                    Some((prev_candidates, prev_costs)) if !prev_candidates.is_empty() => prev_candidates.iter()
+                        .zip(prev_costs.iter())
+                        .enumerate()
+                        .map(|(j, (p, (acc, _)))| (acc + self.concatenation_cost(p, c) + c.target_cost, Some(j)))
+                        .min_by(|a, b| a.0.total_cmp(&b.0))
+                        .unwrap(),
+                    _ => (c.target_cost, None)
+                }
+            }).collect();
+
+            costs.push(row);
+        }
+
+        // Trace back from the end; a position without candidates restarts the chain
+        let mut result = vec![None; targets.len()];
+        let mut next: Option<usize> = None;
+        for i in (0..targets.len()).rev() {
+            let best = next.or_else(|| (0..costs[i].len()).min_by(|a, b| costs[i][*a].0.total_cmp(&costs[i][*b].0)));
+
+            next = None;
+            if let Some(j) = best {
+                let candidate = &candidates[i][j];
+                let previous = costs[i][j].1.map(|p| &candidates[i - 1][p]);
+
+                result[i] = Some(Selection {
+                    entry: candidate.entry,
+                    level: candidate.level,
+                    cost: candidate.target_cost + previous.map_or(0.0, |p| self.concatenation_cost(p, candidate))
+                });
+
+                next = costs[i][j].1;
+            }
+        }
+
+        result
+    }
+
+    fn candidates(&self, targets: &[Target], i: usize) -> Vec<Candidate<'a>> {
+        let target = &targets[i];
+        let prev = if i > 0 { targets[i - 1].phoneme.as_str() } else { "sil" };
+        let next = targets.get(i + 1).map_or("sil", |t| t.phoneme.as_str());
+
+        for level in self.config.fallback.iter() {
+            let entries = match level {
+                MatchLevel::Triphone => self.index.query(Context::Triphone(prev, &target.phoneme, next), &self.config.filter),
+                MatchLevel::Diphone => self.index.query(Context::Diphone(&target.phoneme, next), &self.config.filter),
+                MatchLevel::Monophone => self.index.query(Context::Monophone(&target.phoneme), &self.config.filter),
+                MatchLevel::Similar => self.similar_phonemes(&target.phoneme).into_iter()
+                    .flat_map(|p| self.index.query(Context::Monophone(p), &self.config.filter))
+                    .collect()
+            };
+
+            if entries.is_empty() {
+                continue;
+            }
+
+            let mut candidates = entries.into_iter().map(|entry| Candidate {
+                entry,
+                level: *level,
+                target_cost: self.target_cost(target, prev, next, &entry)
+            }).collect::<Vec<_>>();
+
+            candidates.sort_by(|a, b| a.target_cost.total_cmp(&b.target_cost));
+            candidates.truncate(self.config.max_candidates);
+
+            return candidates;
+        }
+
+        vec![]
+    }
+
+    fn similar_phonemes<'s>(&'s self, phoneme: &str) -> BTreeSet<&'s str> {
+        let mut phonemes = self.config.similar.iter()
+            .filter(|class| class.iter().any(|p| p == phoneme))
+            .flatten()
+            .map(|p| p.as_str())
+            .collect::<BTreeSet<&str>>();
+
+        if let Some(max) = self.config.similar_distance {
+            phonemes.extend(self.index.phonemes().filter(|p| phoneme_distance(phoneme, p).is_some_and(|d| d <= max)));
+        }
+
+        phonemes.remove(phoneme);
+        phonemes
+    }
+
+    fn target_cost(&self, target: &Target, prev: &str, next: &str, entry: &IndexEntry) -> f64 {
+        let weights = &self.config.weights;
+        let utterance = entry.utterance;
+
+        let mismatches = [utterance.prev != prev, utterance.next != next, utterance.curr != target.phoneme];
+        let context = mismatches.iter().filter(|m| **m).count() as f64;
+
+        let pitch = match (target.pitch, utterance.pitch) {
+            (Some(t), Some(c)) => t.abs_diff(c) as f64 / 12.0,
+            (Some(_), None) => 1.0,
+            _ => 0.0
+        };
+
+        let duration = match target.duration {
+            Some(d) if d.value > 0 && utterance.length().value > 0 => (utterance.length().value as f64 / d.value as f64).ln().abs(),
+            _ => 0.0
+        };
+
+        weights.context * context + weights.pitch * pitch + weights.duration * duration
+    }
+
+    fn concatenation_cost(&self, a: &Candidate, b: &Candidate) -> f64 {
+        let contiguous = std::ptr::eq(a.entry.file, b.entry.file) && a.entry.utterance.end == b.entry.utterance.start;
+        if contiguous {
+            return 0.0;
+        }
+
+        let jump = match (a.entry.utterance.pitch, b.entry.utterance.pitch) {
+            (Some(p), Some(q)) => p.abs_diff(q) as f64 / 12.0,
+            _ => 0.0
+        };
+
+        self.config.weights.concatenation * (1.0 + jump)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utterance::{FileDescriptor, Utterance};
+
+    fn label(prev: &str, curr: &str, next: &str, start: i64, end: i64) -> Utterance {
+        Utterance {
+            prev: prev.to_string(),
+            curr: curr.to_string(),
+            next: next.to_string(),
+            start: Timestamp::new(start),
+            end: Timestamp::new(end),
+            pitch: Some(60),
+            ..Default::default()
+        }
+    }
+
+    fn target(phoneme: &str) -> Target {
+        Target {
+            phoneme: phoneme.to_string(),
+            pitch: Some(60),
+            duration: None
+        }
+    }
+
+    #[test]
+    fn test_select_with_fallback() {
+        let library = Library {
+            files: vec![
+                FileDescriptor {
+                    labels: vec![label("sil", "k", "a", 0, 100), label("k", "a", "sil", 100, 300)],
+                    ..Default::default()
+                },
+                FileDescriptor {
+                    labels: vec![label("sil", "g", "o", 0, 100), label("g", "o", "sil", 100, 300)],
+                    ..Default::default()
+                }
+            ],
+            ..Default::default()
+        };
+
+        let config = SelectionConfig {
+            similar: vec![vec![String::from("k"), String::from("g")]],
+            ..Default::default()
+        };
+        let selector = UnitSelector::from_library(&library, config);
+
+        // Exact triphones, taken from one recording
+        let selection = selector.select(&[target("k"), target("a")]);
+        assert!(selection.iter().all(|s| s.unwrap().level == MatchLevel::Triphone));
+        assert!(selection.iter().all(|s| s.unwrap().cost == 0.0));
+
+        // "a" before "o" falls back to a monophone, "o" before silence to a diphone
+        let selection = selector.select(&[target("a"), target("o")]);
+        assert_eq!(selection[0].unwrap().level, MatchLevel::Monophone);
+        assert_eq!(selection[1].unwrap().level, MatchLevel::Diphone);
+
+        // "x" has no candidate at all
+        let selection = selector.select(&[target("a"), target("x")]);
+        assert!(selection[1].is_none());
+
+        // "g" is only in the library as part of a similarity class for "k"
+        let library = Library {
+            files: vec![library.files[1].clone()],
+            ..Default::default()
+        };
+        let selector = UnitSelector::from_library(&library, selector.config.clone());
+        let selection = selector.select(&[target("k")]);
+        assert_eq!(selection[0].unwrap().level, MatchLevel::Similar);
+        assert_eq!(selection[0].unwrap().entry.utterance.curr, "g");
+
+        // Without a class, "k" is still one voicing step away from "g"
+        let config = SelectionConfig {
+            similar_distance: Some(0.5),
+            ..Default::default()
+        };
+        let selector = UnitSelector::from_library(&library, config);
+        assert_eq!(selector.select(&[target("k")])[0].unwrap().entry.utterance.curr, "g");
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use anyhow::Result;
 use usid::USID;
-use crate::{library::Library, utterance::FileDescriptor};
+use crate::{index::UtteranceIndex, library::Library, tools::{convert::Convert, pitch::PitchConfig, Phoneset}, utterance::FileDescriptor};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Singer {
@@ -20,7 +20,7 @@ pub struct Singer {
 }
 
 impl Singer {
-    #[cfg(feature = "generator")]
+    #[cfg(feature = "generator-core")]
     pub fn new() -> Self {
         Singer {
             meta: Meta::default(),
@@ -45,9 +45,24 @@ impl Singer {
         }
     }
 
+    // Loads a singer and converts every library that is not yet written in the requested phoneset
+    pub fn load_as<P: AsRef<Path>>(path: P, phoneset: Phoneset) -> Result<Self> {
+        let mut singer = Self::load(path)?;
+
+        for library in singer.libraries.iter_mut() {
+            if library.phoneset != phoneset {
+                library.convert(library.phoneset.clone(), phoneset.clone())?;
+            }
+        }
+
+        Ok(singer)
+    }
+
     fn load_bin<P: AsRef<Path>>(path: P) -> Result<Self> {
         let data = std::fs::read(path)?;
-        Ok(bincode::deserialize(&data)?)
+
+        // Binary singers written before libraries recorded their phoneset lack the field and fail here
+        bincode::deserialize(&data).map_err(|e| anyhow::anyhow!("Failed to decode binary singer ({}); files saved by 0.1.x must be re-saved from JSON", e))
     }
 
     fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -95,6 +110,10 @@ impl Singer {
         Ok(())
     }
 
+    pub fn index(&self) -> UtteranceIndex<'_> {
+        UtteranceIndex::from_singer(self)
+    }
+
     pub fn get_default(&self) -> Option<&Library> {
         let r0 = self.libraries.iter().find(|lib| lib.is_default);
 
@@ -118,11 +137,15 @@ pub struct Origin {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub publisher: Option<Author>,
     pub application: String,
-    pub creation_date: String
+    pub creation_date: String,
+
+    // Settings used to fill the labels' pitch fields when the singer was generated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pitch_analysis: Option<PitchConfig>
 }
 
 impl Origin {
-    #[cfg(feature = "generator")]
+    #[cfg(feature = "generator-core")]
     pub fn now() -> Self {
         Origin {
             application: String::from("OpenVBgen"),
@@ -1,7 +1,33 @@
 // Timestamp contains a time value in microseconds.
+// Arithmetic saturates at the i64 range instead of overflowing; the checked_* methods report overflow instead.
 
+use anyhow::{Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
 
+// How fractional microseconds (or samples) are turned into whole ones
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    #[default]
+    Nearest,
+    Floor,
+    Ceil,
+    Truncate
+}
+
+impl Rounding {
+    // Out of range values saturate, NaN becomes 0
+    pub fn apply(self, value: f64) -> i64 {
+        let rounded = match self {
+            Rounding::Nearest => value.round(),
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+            Rounding::Truncate => value.trunc()
+        };
+
+        rounded as i64
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Timestamp {
     pub value: i64
@@ -24,8 +50,11 @@ impl Timestamp {
         self.value as f64 / 1_000_000.0
     }
     pub fn from_seconds(seconds: f64) -> Self {
+        Self::from_seconds_with(seconds, Rounding::Nearest)
+    }
+    pub fn from_seconds_with(seconds: f64, rounding: Rounding) -> Self {
         Timestamp {
-            value: (seconds * 1_000_000.0) as i64
+            value: rounding.apply(seconds * 1_000_000.0)
         }
     }
 
@@ -33,17 +62,28 @@ impl Timestamp {
         self.value as f64 / 1_000.0
     }
     pub fn from_milliseconds(milliseconds: f64) -> Self {
+        Self::from_milliseconds_with(milliseconds, Rounding::Nearest)
+    }
+    pub fn from_milliseconds_with(milliseconds: f64, rounding: Rounding) -> Self {
         Timestamp {
-            value: (milliseconds * 1_000.0) as i64
+            value: rounding.apply(milliseconds * 1_000.0)
         }
     }
 
+    // Both directions round to the nearest value, so samples -> Timestamp -> samples is exact for any rate below 1 MHz.
+    // Negative timestamps are clamped to sample 0.
     pub fn samples(&self, sample_rate: f64) -> usize {
-        (self.value as f64 * sample_rate / 1_000_000.0).round() as usize
+        self.samples_with(sample_rate, Rounding::Nearest)
+    }
+    pub fn samples_with(&self, sample_rate: f64, rounding: Rounding) -> usize {
+        rounding.apply(self.value as f64 * sample_rate / 1_000_000.0).max(0) as usize
     }
     pub fn from_samples(samples: usize, sample_rate: f64) -> Self {
+        Self::from_samples_with(samples, sample_rate, Rounding::Nearest)
+    }
+    pub fn from_samples_with(samples: usize, sample_rate: f64, rounding: Rounding) -> Self {
         Timestamp {
-            value: (samples as f64 * 1_000_000.0 / sample_rate) as i64
+            value: rounding.apply(samples as f64 * 1_000_000.0 / sample_rate)
         }
     }
 
@@ -59,9 +99,7 @@ impl std::ops::Add<Timestamp> for Timestamp {
     type Output = Timestamp;
 
     fn add(self, other: Timestamp) -> Timestamp {
-        Timestamp {
-            value: self.value + other.value
-        }
+        self.saturating_add(other)
     }
 }
 
@@ -69,21 +107,19 @@ impl std::ops::Sub<Timestamp> for Timestamp {
     type Output = Timestamp;
 
     fn sub(self, other: Timestamp) -> Timestamp {
-        Timestamp {
-            value: self.value - other.value
-        }
+        self.saturating_sub(other)
     }
 }
 
 impl std::ops::AddAssign<Timestamp> for Timestamp {
     fn add_assign(&mut self, other: Timestamp) {
-        self.value += other.value;
+        *self = *self + other;
     }
 }
 
 impl std::ops::SubAssign<Timestamp> for Timestamp {
     fn sub_assign(&mut self, other: Timestamp) {
-        self.value -= other.value;
+        *self = *self - other;
     }
 }
 
@@ -92,7 +128,7 @@ impl std::ops::Mul<f64> for Timestamp {
 
     fn mul(self, other: f64) -> Timestamp {
         Timestamp {
-            value: (self.value as f64 * other) as i64
+            value: Rounding::Nearest.apply(self.value as f64 * other)
         }
     }
 }
@@ -102,36 +138,81 @@ impl std::ops::Div<f64> for Timestamp {
 
     fn div(self, other: f64) -> Timestamp {
         Timestamp {
-            value: (self.value as f64 / other) as i64
+            value: Rounding::Nearest.apply(self.value as f64 / other)
         }
     }
 }
 
 impl std::ops::MulAssign<f64> for Timestamp {
     fn mul_assign(&mut self, other: f64) {
-        self.value = (self.value as f64 * other) as i64;
+        *self = *self * other;
     }
 }
 
 impl std::ops::DivAssign<f64> for Timestamp {
     fn div_assign(&mut self, other: f64) {
-        self.value = (self.value as f64 / other) as i64;
+        *self = *self / other;
     }
 }
 
+// The remainder is always in 0..|other|, so negative timestamps wrap into the period as well.
+// A zero period leaves the timestamp unchanged; use `checked_rem` to detect it.
 impl std::ops::Rem<Timestamp> for Timestamp {
     type Output = Timestamp;
 
     fn rem(self, other: Timestamp) -> Timestamp {
-        Timestamp {
-            value: self.value % other.value
-        }
+        self.checked_rem(other).unwrap_or(self)
     }
 }
 
 impl std::ops::RemAssign<Timestamp> for Timestamp {
     fn rem_assign(&mut self, other: Timestamp) {
-        self.value %= other.value;
+        *self = *self % other;
+    }
+}
+
+// Checked and saturating arithmetic
+impl Timestamp {
+    pub fn checked_add(self, other: Timestamp) -> Option<Timestamp> {
+        self.value.checked_add(other.value).map(Timestamp::new)
+    }
+
+    pub fn checked_sub(self, other: Timestamp) -> Option<Timestamp> {
+        self.value.checked_sub(other.value).map(Timestamp::new)
+    }
+
+    pub fn saturating_add(self, other: Timestamp) -> Timestamp {
+        Timestamp::new(self.value.saturating_add(other.value))
+    }
+
+    pub fn saturating_sub(self, other: Timestamp) -> Timestamp {
+        Timestamp::new(self.value.saturating_sub(other.value))
+    }
+
+    // None if the factor is not finite or the result does not fit
+    pub fn checked_mul(self, other: f64) -> Option<Timestamp> {
+        checked_from_f64((self.value as f64 * other).round())
+    }
+
+    pub fn checked_div(self, other: f64) -> Option<Timestamp> {
+        if other == 0.0 {
+            return None;
+        }
+
+        checked_from_f64((self.value as f64 / other).round())
+    }
+
+    pub fn checked_rem(self, other: Timestamp) -> Option<Timestamp> {
+        self.value.checked_rem_euclid(other.value).map(Timestamp::new)
+    }
+}
+
+fn checked_from_f64(value: f64) -> Option<Timestamp> {
+    // i64::MAX is not representable as f64; the nearest f64 above it is 2^63
+    if value.is_finite() && value >= i64::MIN as f64 && value < i64::MAX as f64 {
+        Some(Timestamp::new(value as i64))
+    } else {
+        None
     }
 }
 
@@ -153,16 +234,143 @@ impl Timestamp {
         }
     }
 
+    pub fn abs(self) -> Timestamp {
+        Timestamp::new(self.value.saturating_abs())
+    }
+
     pub fn pow(self, exp: u32) -> Timestamp {
-        Timestamp {
-            value: self.value.pow(exp)
-        }
+        Timestamp::new(self.value.saturating_pow(exp))
     }
 
+    pub fn checked_pow(self, exp: u32) -> Option<Timestamp> {
+        self.value.checked_pow(exp).map(Timestamp::new)
+    }
+
+    // Negative values have no square root and give zero; use `checked_sqrt` to detect them
     pub fn sqrt(self) -> Timestamp {
-        Timestamp {
-            value: (self.value as f64).sqrt() as i64
+        self.checked_sqrt().unwrap_or_default()
+    }
+
+    pub fn checked_sqrt(self) -> Option<Timestamp> {
+        if self.value < 0 {
+            return None;
         }
+
+        Some(Timestamp::new((self.value as f64).sqrt() as i64))
+    }
+}
+
+// A half-open interval [start, end), serialized as `[start, end]` like `Utterance::areas` always was
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "[Timestamp; 2]", into = "[Timestamp; 2]")]
+pub struct TimeRange {
+    pub start: Timestamp,
+    pub end: Timestamp
+}
+
+impl TimeRange {
+    // The bounds are swapped if given in the wrong order
+    pub fn new(start: Timestamp, end: Timestamp) -> Self {
+        TimeRange {
+            start: start.min(end),
+            end: start.max(end)
+        }
+    }
+
+    pub fn length(&self) -> Timestamp {
+        self.end - self.start
+    }
+
+    pub fn midpoint(&self) -> Timestamp {
+        Timestamp::new(self.start.value + (self.end.value - self.start.value) / 2)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    pub fn contains(&self, time: Timestamp) -> bool {
+        self.start <= time && time < self.end
+    }
+
+    pub fn contains_range(&self, other: &TimeRange) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn overlaps(&self, other: &TimeRange) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    // None if the ranges do not overlap
+    pub fn intersection(&self, other: &TimeRange) -> Option<TimeRange> {
+        let range = TimeRange {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end)
+        };
+
+        Some(range).filter(|r| !r.is_empty())
+    }
+
+    // None if there is a gap between the ranges; use `hull` to span it
+    pub fn union(&self, other: &TimeRange) -> Option<TimeRange> {
+        if self.start > other.end || other.start > self.end {
+            return None;
+        }
+
+        Some(self.hull(other))
+    }
+
+    pub fn hull(&self, other: &TimeRange) -> TimeRange {
+        TimeRange {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end)
+        }
+    }
+
+    // Negative padding shrinks the range, down to an empty range at its midpoint
+    pub fn pad(&self, before: Timestamp, after: Timestamp) -> TimeRange {
+        let start = self.start - before;
+        let end = self.end + after;
+
+        if start > end {
+            let midpoint = TimeRange::new(start, end).midpoint();
+            return TimeRange::new(midpoint, midpoint);
+        }
+
+        TimeRange::new(start, end)
+    }
+
+    // None unless the point lies strictly inside the range
+    pub fn split_at(&self, time: Timestamp) -> Option<(TimeRange, TimeRange)> {
+        if time <= self.start || time >= self.end {
+            return None;
+        }
+
+        Some((TimeRange::new(self.start, time), TimeRange::new(time, self.end)))
+    }
+
+    // Sample indices covered by the range, with both bounds rounded to the nearest sample
+    pub fn samples(&self, sample_rate: f64) -> std::ops::Range<usize> {
+        self.start.samples(sample_rate)..self.end.samples(sample_rate)
+    }
+
+    pub fn from_samples(samples: std::ops::Range<usize>, sample_rate: f64) -> Self {
+        TimeRange::new(Timestamp::from_samples(samples.start, sample_rate), Timestamp::from_samples(samples.end, sample_rate))
+    }
+}
+
+impl From<[Timestamp; 2]> for TimeRange {
+    fn from(value: [Timestamp; 2]) -> Self {
+        TimeRange {
+            start: value[0],
+            end: value[1]
+        }
+    }
+}
+
+impl From<TimeRange> for [Timestamp; 2] {
+    fn from(value: TimeRange) -> Self {
+        [value.start, value.end]
     }
 }
 
@@ -179,12 +387,303 @@ impl From<i64> for Timestamp {
 impl std::fmt::Display for Timestamp {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         // Depending on the value, we will display the time in seconds, milliseconds or microseconds
-        if self.value >= 1_000_000 {
+        let magnitude = self.value.unsigned_abs();
+        if magnitude >= 1_000_000 {
             write!(f, "{:.3}s", self.seconds())
-        } else if self.value >= 1_000 {
+        } else if magnitude >= 1_000 {
             write!(f, "{:.3}ms", self.milliseconds())
         } else {
             write!(f, "{}µs", self.value)
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    // Same as Display: `1.234s`, `5.000ms`, `12µs`
+    Units,
+    // The largest unit that represents the value exactly: `2s`, `1500ms`, `12µs`
+    Exact,
+    // `hh:mm:ss.mmm`
+    Clock,
+    // `hh:mm:ss:ff`, non-drop-frame
+    Smpte { fps: u32 },
+    // HTK label units of 100ns
+    Htk,
+    Samples { sample_rate: u32 }
+}
+
+impl Timestamp {
+    pub fn htk(&self) -> i64 {
+        self.value.saturating_mul(10)
+    }
+    pub fn from_htk(value: i64) -> Self {
+        Timestamp {
+            value: Rounding::Nearest.apply(value as f64 / 10.0)
+        }
+    }
+
+    pub fn format(&self, format: TimeFormat) -> String {
+        let sign = if self.value < 0 { "-" } else { "" };
+        let magnitude = self.value.unsigned_abs();
+
+        match format {
+            TimeFormat::Units => self.to_string(),
+            TimeFormat::Exact => match magnitude {
+                0 => String::from("0s"),
+                m if m % 1_000_000 == 0 => format!("{}{}s", sign, m / 1_000_000),
+                m if m % 1_000 == 0 => format!("{}{}ms", sign, m / 1_000),
+                m => format!("{}{}µs", sign, m)
+            },
+            TimeFormat::Clock => {
+                let milliseconds = (magnitude + 500) / 1_000;
+                let (hours, minutes, seconds) = (milliseconds / 3_600_000, milliseconds / 60_000 % 60, milliseconds / 1_000 % 60);
+                format!("{}{:02}:{:02}:{:02}.{:03}", sign, hours, minutes, seconds, milliseconds % 1_000)
+            },
+            TimeFormat::Smpte { fps } => {
+                let seconds = magnitude / 1_000_000;
+                let frames = (magnitude % 1_000_000) * fps as u64 / 1_000_000;
+                format!("{}{:02}:{:02}:{:02}:{:02}", sign, seconds / 3_600, seconds / 60 % 60, seconds % 60, frames)
+            },
+            TimeFormat::Htk => self.htk().to_string(),
+            TimeFormat::Samples { sample_rate } => {
+                let samples = Timestamp::new(magnitude.min(i64::MAX as u64) as i64).samples(sample_rate as f64);
+                format!("{}{}", sign, samples)
+            }
+        }
+    }
+
+    // Units and Exact accept anything `FromStr` does
+    pub fn parse(text: &str, format: TimeFormat) -> Result<Timestamp> {
+        let text = text.trim();
+        let (negative, magnitude) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text)
+        };
+
+        let timestamp = match format {
+            TimeFormat::Units | TimeFormat::Exact => return text.parse(),
+            TimeFormat::Clock => parse_clock(magnitude)?,
+            TimeFormat::Smpte { fps } => {
+                let parts = magnitude.split(':').map(|p| p.parse::<u64>()).collect::<Result<Vec<u64>, _>>()?;
+                let [hours, minutes, seconds, frames] = parts[..] else {
+                    bail!("Expected hh:mm:ss:ff, got {:?}", text);
+                };
+                if fps == 0 || frames >= fps as u64 {
+                    bail!("Frame {} is out of range at {} fps", frames, fps);
+                }
+
+                let micros = whole_seconds(hours, minutes, seconds, text)? as f64 * 1_000_000.0 + frames as f64 * 1_000_000.0 / fps as f64;
+                Timestamp::new(Rounding::Nearest.apply(micros))
+            },
+            TimeFormat::Htk => Timestamp::from_htk(magnitude.parse()?),
+            TimeFormat::Samples { sample_rate } => Timestamp::from_samples(magnitude.parse()?, sample_rate as f64)
+        };
+
+        Ok(if negative { Timestamp::new(-timestamp.value) } else { timestamp })
+    }
+}
+
+// `hh:mm:ss.mmm` or `mm:ss.mmm`
+fn parse_clock(text: &str) -> Result<Timestamp> {
+    let parts = text.split(':').collect::<Vec<&str>>();
+    let (hours, minutes, seconds) = match parts[..] {
+        [hours, minutes, seconds] => (hours.parse::<u64>()?, minutes.parse::<u64>()?, seconds),
+        [minutes, seconds] => (0, minutes.parse::<u64>()?, seconds),
+        _ => bail!("Expected hh:mm:ss.mmm, got {:?}", text)
+    };
+
+    let seconds = seconds.parse::<f64>()?;
+    if !(0.0..60.0).contains(&seconds) || (parts.len() == 3 && minutes >= 60) {
+        bail!("Clock time out of range: {:?}", text);
+    }
+
+    Ok(Timestamp::from_seconds(whole_seconds(hours, minutes, 0, text)? as f64 + seconds))
+}
+
+// Hours, minutes and seconds in seconds, failing instead of overflowing on absurd input
+fn whole_seconds(hours: u64, minutes: u64, seconds: u64, text: &str) -> Result<u64> {
+    hours.checked_mul(3_600)
+        .and_then(|h| minutes.checked_mul(60).and_then(|m| h.checked_add(m)))
+        .and_then(|t| t.checked_add(seconds))
+        .ok_or(anyhow!("Clock time out of range: {:?}", text))
+}
+
+// A number with a unit (`h`, `min`, `s`, `ms`, `µs`/`us`), a clock time (`00:01:02.500`), or a bare
+// integer number of microseconds
+impl std::str::FromStr for Timestamp {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.contains(':') {
+            let (negative, magnitude) = match text.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, text)
+            };
+
+            let timestamp = parse_clock(magnitude)?;
+            return Ok(if negative { Timestamp::new(-timestamp.value) } else { timestamp });
+        }
+
+        const UNITS: [(&str, f64); 6] = [("µs", 1.0), ("us", 1.0), ("ms", 1_000.0), ("min", 60_000_000.0), ("s", 1_000_000.0), ("h", 3_600_000_000.0)];
+
+        for (suffix, scale) in UNITS {
+            if let Some(number) = text.strip_suffix(suffix) {
+                let number = number.trim().parse::<f64>().map_err(|_| anyhow!("Invalid time: {:?}", text))?;
+                if !number.is_finite() {
+                    bail!("Invalid time: {:?}", text);
+                }
+
+                return Ok(Timestamp::new(Rounding::Nearest.apply(number * scale)));
+            }
+        }
+
+        text.parse::<i64>().map(Timestamp::new).map_err(|_| anyhow!("Invalid time: {:?}", text))
+    }
+}
+
+// Serde adapter for `#[serde(with = "openvb::time::human")]`. Writes the exact unit form (`"120ms"`) and reads
+// anything `FromStr` accepts, a bare number of microseconds, or the plain `{"value": ...}` layout.
+pub mod human {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{TimeFormat, Timestamp};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Text(String),
+        Microseconds(i64),
+        Plain(Timestamp)
+    }
+
+    pub fn serialize<S: Serializer>(timestamp: &Timestamp, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&timestamp.format(TimeFormat::Exact))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+            Repr::Microseconds(value) => Ok(Timestamp::new(value)),
+            Repr::Plain(timestamp) => Ok(timestamp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATES: [f64; 3] = [44_100.0, 48_000.0, 96_000.0];
+
+    #[test]
+    fn test_samples_roundtrip() {
+        for sample_rate in SAMPLE_RATES {
+            // Every sample of the first second, then a sweep up to a few hours
+            let samples = (0..sample_rate as usize).chain((0..10_000).map(|i| i * 104_729 + 7));
+
+            for n in samples {
+                let timestamp = Timestamp::from_samples(n, sample_rate);
+                assert_eq!(timestamp.samples(sample_rate), n, "{} samples at {} Hz", n, sample_rate);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        // One sample at 44.1 kHz is 22.675... µs
+        let one = |rounding| Timestamp::from_samples_with(1, 44_100.0, rounding).value;
+        assert_eq!((one(Rounding::Nearest), one(Rounding::Floor), one(Rounding::Ceil), one(Rounding::Truncate)), (23, 22, 23, 22));
+
+        assert_eq!(Timestamp::from_milliseconds(-0.0006).value, -1);
+        assert_eq!(Timestamp::from_milliseconds_with(-0.0006, Rounding::Truncate).value, 0);
+        assert_eq!(Timestamp::from_seconds(0.0000015).value, 2);
+        assert_eq!(Timestamp::new(-5).samples(48_000.0), 0);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let max = Timestamp::new(i64::MAX);
+        assert_eq!(max + Timestamp::new(1), max);
+        assert!(max.checked_add(Timestamp::new(1)).is_none());
+        assert!(Timestamp::new(i64::MIN).checked_sub(Timestamp::new(1)).is_none());
+
+        assert_eq!(max * 2.0, max);
+        assert!(max.checked_mul(2.0).is_none());
+        assert!(Timestamp::new(1).checked_div(0.0).is_none());
+
+        assert_eq!(Timestamp::new(10).pow(30), max);
+        assert_eq!(Timestamp::new(-16).sqrt(), Timestamp::zero());
+        assert!(Timestamp::new(-16).checked_sqrt().is_none());
+
+        assert_eq!(Timestamp::new(-3) % Timestamp::new(10), Timestamp::new(7));
+        assert_eq!(Timestamp::new(3) % Timestamp::zero(), Timestamp::new(3));
+        assert!(Timestamp::new(3).checked_rem(Timestamp::zero()).is_none());
+    }
+
+    #[test]
+    fn test_time_range() {
+        let range = |start, end| TimeRange::new(Timestamp::new(start), Timestamp::new(end));
+
+        let a = range(100, 0);
+        assert_eq!((a.start.value, a.end.value), (0, 100));
+        assert!(a.contains(Timestamp::new(0)) && !a.contains(Timestamp::new(100)));
+
+        assert_eq!(a.intersection(&range(50, 150)), Some(range(50, 100)));
+        assert_eq!(a.intersection(&range(100, 150)), None);
+        assert_eq!(a.union(&range(100, 150)), Some(range(0, 150)));
+        assert_eq!(a.union(&range(120, 150)), None);
+        assert!(a.contains_range(&range(10, 20)));
+
+        assert_eq!(a.pad(Timestamp::new(10), Timestamp::new(10)), range(-10, 110));
+        assert_eq!(a.pad(Timestamp::new(-80), Timestamp::new(-80)), range(50, 50));
+        assert_eq!(a.split_at(Timestamp::new(30)), Some((range(0, 30), range(30, 100))));
+        assert_eq!(a.split_at(Timestamp::new(100)), None);
+
+        let samples = TimeRange::from_samples(441..882, 44_100.0);
+        assert_eq!(samples.samples(44_100.0), 441..882);
+
+        // Same layout as the `[Timestamp; 2]` areas used to have
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, serde_json::to_string(&[Timestamp::new(0), Timestamp::new(100)]).unwrap());
+        assert_eq!(serde_json::from_str::<TimeRange>(&json).unwrap(), a);
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let t = Timestamp::new(62_500_000);
+
+        assert_eq!(t.format(TimeFormat::Clock), "00:01:02.500");
+        assert_eq!(t.format(TimeFormat::Smpte { fps: 30 }), "00:01:02:15");
+        assert_eq!(t.format(TimeFormat::Htk), "625000000");
+        assert_eq!(t.format(TimeFormat::Samples { sample_rate: 48_000 }), "3000000");
+        assert_eq!(t.format(TimeFormat::Exact), "62500ms");
+
+        for format in [TimeFormat::Exact, TimeFormat::Clock, TimeFormat::Smpte { fps: 30 }, TimeFormat::Htk, TimeFormat::Samples { sample_rate: 48_000 }] {
+            assert_eq!(Timestamp::parse(&t.format(format), format).unwrap(), t, "{:?}", format);
+        }
+
+        assert_eq!("120ms".parse::<Timestamp>().unwrap(), Timestamp::new(120_000));
+        assert_eq!("1.5 s".parse::<Timestamp>().unwrap(), Timestamp::new(1_500_000));
+        assert_eq!("2min".parse::<Timestamp>().unwrap(), Timestamp::from_seconds(120.0));
+        assert_eq!("-12µs".parse::<Timestamp>().unwrap(), Timestamp::new(-12));
+        assert_eq!("00:01:02.500".parse::<Timestamp>().unwrap(), t);
+        assert_eq!("1.234s".parse::<Timestamp>().unwrap().to_string(), "1.234s");
+        assert!("12 parsecs".parse::<Timestamp>().is_err());
+        assert!("00:61:00".parse::<Timestamp>().is_err());
+        assert!("18446744073709551615:00:00".parse::<Timestamp>().is_err());
+        assert!(Timestamp::parse("18446744073709551615:00:00:00", TimeFormat::Smpte { fps: 25 }).is_err());
+
+        #[derive(Serialize, Deserialize)]
+        struct Config {
+            #[serde(with = "human")]
+            time: Timestamp
+        }
+
+        let config: Config = serde_json::from_str(r#"{"time": "00:00:01.250"}"#).unwrap();
+        assert_eq!(serde_json::to_string(&config).unwrap(), r#"{"time":"1250ms"}"#);
+        let config: Config = serde_json::from_str(r#"{"time": {"value": 5}}"#).unwrap();
+        assert_eq!(config.time, Timestamp::new(5));
+    }
+}
@@ -1,87 +1,181 @@
-
-
-// use crate::{encode::PhonemeEncoder, library::Library, utterance::{FileDescriptor, Utterance}, Singer};
-
-// pub trait FromARPA {
-//     fn from_arpa(&mut self);
-// }
-
-// pub trait ToARPA {
-//     fn to_arpa(&mut self);
-// }
-
-// impl FromARPA for Singer {
-//     fn from_arpa(&mut self) {
-//         for library in self.libraries.iter_mut() {
-//             library.from_arpa();
-//         }
-//     }
-// }
-
-// impl FromARPA for Library {
-//     fn from_arpa(&mut self) {
-//         for file in self.files.iter_mut() {
-//             file.from_arpa();
-//         }
-//     }
-// }
-
-// impl FromARPA for FileDescriptor {
-//     fn from_arpa(&mut self) {
-//         for label in self.labels.iter_mut() {
-//             label.from_arpa();
-//         }
-//     }
-// }
-
-// impl FromARPA for Utterance {
-//     fn from_arpa(&mut self) {
-//         if !PhonemeEncoder::is_silence(&self.prev) {
-//             self.prev = ipa_to_xsampa(&self.prev);
-//         }
-
-//         self.curr = ipa_to_xsampa(&self.curr);
-
-//         if !PhonemeEncoder::is_silence(&self.next) {
-//             self.next = ipa_to_xsampa(&self.next);
-//         }
-//     }
-// }
-
-// impl ToARPA for Singer {
-//     fn to_arpa(&mut self) {
-//         for library in self.libraries.iter_mut() {
-//             library.to_arpa();
-//         }
-//     }
-// }
-
-// impl ToARPA for Library {
-//     fn to_arpa(&mut self) {
-//         for file in self.files.iter_mut() {
-//             file.to_arpa();
-//         }
-//     }
-// }
-
-// impl ToARPA for FileDescriptor {
-//     fn to_arpa(&mut self) {
-//         for label in self.labels.iter_mut() {
-//             label.to_arpa();
-//         }
-//     }
-// }
-
-// impl ToARPA for Utterance {
-//     fn to_arpa(&mut self) {
-//         if !PhonemeEncoder::is_silence(&self.prev) {
-//             self.prev = xsampa_to_arpa(&self.prev);
-//         }
-
-//         self.curr = xsampa_to_arpa(&self.curr);
-
-//         if !PhonemeEncoder::is_silence(&self.next) {
-//             self.next = xsampa_to_arpa(&self.next);
-//         }
-//     }
-// }
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::{tools::{convert::{ConversionReport, Convert, PhonemeMapping}, Phoneset}, utterance::Utterance};
+
+// ARPAbet to X-SAMPA. Stressed and unstressed variants only get their own entry where X-SAMPA
+// distinguishes them (AH0/AH, ER0/ER); for the reverse direction the first matching entry wins.
+pub(crate) const ARPA_DATA: [(&str, &str); 48] = [
+    // Vowels
+    ("AA", "A"), ("AE", "{"), ("AH0", "@"), ("AH", "V"), ("AO", "O"), ("AW", "aU"), ("AY", "aI"),
+    ("EH", "E"), ("ER0", "@`"), ("ER", "3`"), ("EY", "eI"), ("IH", "I"), ("IY", "i"), ("OW", "oU"),
+    ("OY", "OI"), ("UH", "U"), ("UW", "u"),
+
+    // Consonants
+    ("B", "b"), ("CH", "tS"), ("D", "d"), ("DH", "D"), ("F", "f"), ("G", "g"), ("HH", "h"),
+    ("JH", "dZ"), ("K", "k"), ("L", "l"), ("M", "m"), ("N", "n"), ("NG", "N"), ("P", "p"),
+    ("R", "r\\"), ("S", "s"), ("SH", "S"), ("T", "t"), ("TH", "T"), ("V", "v"), ("W", "w"),
+    ("Y", "j"), ("Z", "z"), ("ZH", "Z"),
+
+    // Extended ARPAbet
+    ("AX", "@"), ("AXR", "@`"), ("IX", "1"), ("UX", "}"), ("DX", "4"), ("Q", "?"), ("WH", "W")
+];
+
+// Key in `Utterance::extras` holding the stress digit of the label's own phoneme
+pub const STRESS_KEY: &str = "stress";
+
+pub trait FromARPA {
+    fn from_arpa(&mut self) -> Result<ConversionReport>;
+}
+
+pub trait ToARPA {
+    fn to_arpa(&mut self) -> Result<ConversionReport>;
+}
+
+impl<T: Convert> FromARPA for T {
+    fn from_arpa(&mut self) -> Result<ConversionReport> {
+        self.convert(Phoneset::Arpa, Phoneset::XSampa)
+    }
+}
+
+impl<T: Convert> ToARPA for T {
+    fn to_arpa(&mut self) -> Result<ConversionReport> {
+        self.convert(Phoneset::XSampa, Phoneset::Arpa)
+    }
+}
+
+// Stress digits have no X-SAMPA symbol, so the stress of each label's own phoneme is kept in its extras. The stress
+// of its neighbours is dropped and reported.
+pub struct ArpaMapping;
+
+impl PhonemeMapping for ArpaMapping {
+    fn to_xsampa(&self, symbol: &str) -> Option<String> {
+        let upper = symbol.to_uppercase();
+
+        ARPA_DATA.iter()
+            .find(|(a, _)| *a == upper)
+            .or_else(|| ARPA_DATA.iter().find(|(a, _)| *a == split_stress(&upper).0))
+            .map(|(_, x)| x.to_string())
+    }
+
+    fn xsampa_to_native(&self, symbol: &str) -> Option<String> {
+        ARPA_DATA.iter().find(|(_, x)| *x == symbol).map(|(a, _)| a.to_string())
+    }
+
+    fn before_decode(&self, utterance: &mut Utterance) {
+        if let (_, Some(stress)) = split_stress(&utterance.curr.to_uppercase()) {
+            utterance.extras.get_or_insert_with(HashMap::new).insert(STRESS_KEY.to_string(), stress as f32);
+        }
+    }
+
+    fn after_encode(&self, utterance: &mut Utterance) {
+        let stress = utterance.extras.as_mut().and_then(|e| e.remove(STRESS_KEY));
+        if let Some(stress) = stress {
+            if is_arpa_vowel(&utterance.curr) && split_stress(&utterance.curr).1.is_none() {
+                utterance.curr.push_str(&(stress as u8).to_string());
+            }
+        }
+
+        if utterance.extras.as_ref().is_some_and(|e| e.is_empty()) {
+            utterance.extras = None;
+        }
+    }
+
+    // Stress is only lost where X-SAMPA has no separate symbol for it (AH0 and ER0 have one)
+    fn is_lossy(&self, symbol: &str) -> bool {
+        let upper = symbol.to_uppercase();
+        split_stress(&upper).1.is_some() && !ARPA_DATA.iter().any(|(a, _)| *a == upper)
+    }
+}
+
+// Converts a single ARPAbet phoneme to X-SAMPA. Unknown symbols are returned unchanged.
+pub fn arpa_to_xsampa(symbol: &str) -> String {
+    ArpaMapping.to_xsampa(symbol).unwrap_or(symbol.to_string())
+}
+
+// Converts a single X-SAMPA phoneme to ARPAbet. Unknown symbols are returned unchanged.
+pub fn xsampa_to_arpa(symbol: &str) -> String {
+    ArpaMapping.xsampa_to_native(symbol).unwrap_or(symbol.to_string())
+}
+
+// Splits an ARPAbet vowel into its base and stress digit (`IY1` -> `IY`, 1)
+pub fn split_stress(symbol: &str) -> (&str, Option<u8>) {
+    match symbol.char_indices().last() {
+        Some((i, c @ '0'..='2')) if i > 0 => (&symbol[..i], c.to_digit(10).map(|d| d as u8)),
+        _ => (symbol, None)
+    }
+}
+
+// All ARPAbet vowels, and no consonants, start with a vowel letter
+fn is_arpa_vowel(symbol: &str) -> bool {
+    symbol.starts_with(['A', 'E', 'I', 'O', 'U'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utterance::PhonemeFlags;
+
+    #[test]
+    fn test_arpa_roundtrip() {
+        for (arpa, xsampa) in ARPA_DATA.iter() {
+            assert_eq!(arpa_to_xsampa(arpa), *xsampa);
+
+            // Symbols shared by several ARPAbet phonemes map back to the first one
+            let back = xsampa_to_arpa(xsampa);
+            assert_eq!(arpa_to_xsampa(&back), *xsampa);
+        }
+    }
+
+    #[test]
+    fn test_arpa_vowels_are_classified() {
+        for (arpa, xsampa) in ARPA_DATA.iter().filter(|(a, _)| is_arpa_vowel(a)) {
+            let flags = PhonemeFlags::classify(xsampa);
+            assert!(flags.contains(PhonemeFlags::VOWEL | PhonemeFlags::VOICED), "{} ({}) is not a voiced vowel", arpa, xsampa);
+        }
+
+        for (arpa, xsampa) in ARPA_DATA.iter().filter(|(a, _)| !is_arpa_vowel(a)) {
+            assert!(!PhonemeFlags::classify(xsampa).contains(PhonemeFlags::VOWEL), "{} ({}) is a vowel", arpa, xsampa);
+        }
+    }
+
+    #[test]
+    fn test_arpa_stress() {
+        assert_eq!(arpa_to_xsampa("AH0"), "@");
+        assert_eq!(arpa_to_xsampa("AH1"), "V");
+        assert_eq!(arpa_to_xsampa("IY1"), "i");
+        assert_eq!(arpa_to_xsampa("er2"), "3`");
+        assert_eq!(split_stress("IY1"), ("IY", Some(1)));
+        assert_eq!(split_stress("T"), ("T", None));
+    }
+
+    #[test]
+    fn test_utterance_stress_roundtrip() {
+        let mut utterance = Utterance {
+            prev: String::from("sil"),
+            curr: String::from("IY1"),
+            next: String::from("T"),
+            ..Default::default()
+        };
+
+        let report = utterance.from_arpa().unwrap();
+        assert_eq!((utterance.prev.as_str(), utterance.curr.as_str(), utterance.next.as_str()), ("sil", "i", "t"));
+        assert!(report.is_lossless());
+
+        utterance.to_arpa().unwrap();
+        assert_eq!((utterance.prev.as_str(), utterance.curr.as_str(), utterance.next.as_str()), ("sil", "IY1", "T"));
+        assert!(utterance.extras.is_none());
+
+        // The stress of a neighbour has nowhere to go
+        let mut utterance = Utterance {
+            prev: String::from("AH0"),
+            curr: String::from("T"),
+            next: String::from("IY1"),
+            ..Default::default()
+        };
+        let report = utterance.from_arpa().unwrap();
+        assert_eq!(report.lossy.into_iter().collect::<Vec<_>>(), vec![(String::from("IY1"), 1)]);
+    }
+}
@@ -0,0 +1,176 @@
+// Audio loading for the analysis tools. Every source is decoded to mono f64 samples in [-1, 1],
+// regardless of channel count, bit depth or container.
+
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use hound::{SampleFormat, WavReader};
+use claxon::FlacReader;
+
+// Number of zero crossings on each side of the resampling kernel
+const SINC_ZEROS: usize = 16;
+
+#[derive(Default, Debug, Clone)]
+pub struct Audio {
+    pub samples: Vec<f64>,
+    pub sample_rate: u32
+}
+
+impl Audio {
+    pub fn load<P: AsRef<Path>>(path: P, target_sample_rate: Option<u32>) -> Result<Self> {
+        let path = path.as_ref();
+        let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+
+        let audio = match ext.as_str() {
+            "wav" => Self::load_wav(path)?,
+            "flac" => Self::load_flac(path)?,
+            _ => bail!("Unsupported file extension: {} ({})", ext, path.display())
+        };
+
+        Ok(match target_sample_rate {
+            Some(rate) => audio.resample(rate),
+            None => audio
+        })
+    }
+
+    fn load_wav(path: &Path) -> Result<Self> {
+        let mut reader = WavReader::open(path)?;
+        let spec = reader.spec();
+
+        let interleaved = match spec.sample_format {
+            SampleFormat::Float => reader.samples::<f32>()
+                .map(|s| s.map(|s| s as f64))
+                .collect::<Result<Vec<f64>, _>>()?,
+            SampleFormat::Int => {
+                let scale = int_scale(spec.bits_per_sample as u32);
+                reader.samples::<i32>()
+                    .map(|s| s.map(|s| s as f64 / scale))
+                    .collect::<Result<Vec<f64>, _>>()?
+            }
+        };
+
+        Ok(Audio {
+            samples: downmix(&interleaved, spec.channels as usize),
+            sample_rate: spec.sample_rate
+        })
+    }
+
+    fn load_flac(path: &Path) -> Result<Self> {
+        let mut reader = FlacReader::open(path)?;
+        let info = reader.streaminfo();
+
+        let scale = int_scale(info.bits_per_sample);
+        let interleaved = reader.samples()
+            .map(|s| s.map(|s| s as f64 / scale))
+            .collect::<Result<Vec<f64>, _>>()?;
+
+        Ok(Audio {
+            samples: downmix(&interleaved, info.channels as usize),
+            sample_rate: info.sample_rate
+        })
+    }
+
+    // Band-limited resampling with a Hann-windowed sinc kernel
+    pub fn resample(self, sample_rate: u32) -> Self {
+        if sample_rate == self.sample_rate || self.samples.is_empty() || sample_rate == 0 {
+            return self;
+        }
+
+        let ratio = sample_rate as f64 / self.sample_rate as f64;
+        let cutoff = ratio.min(1.0);
+        let half_width = SINC_ZEROS as f64 / cutoff;
+
+        let len = (self.samples.len() as f64 * ratio).round() as usize;
+        let samples = (0..len).map(|i| {
+            let center = i as f64 / ratio;
+            let first = (center - half_width).ceil().max(0.0) as usize;
+            let last = ((center + half_width).floor() as usize).min(self.samples.len() - 1);
+
+            (first..=last).map(|j| {
+                let x = j as f64 - center;
+                let window = 0.5 + 0.5 * (std::f64::consts::PI * x / half_width).cos();
+                self.samples[j] * cutoff * sinc(x * cutoff) * window
+            }).sum()
+        }).collect();
+
+        Audio {
+            samples,
+            sample_rate
+        }
+    }
+}
+
+fn int_scale(bits_per_sample: u32) -> f64 {
+    (1u64 << (bits_per_sample.clamp(1, 32) - 1)) as f64
+}
+
+fn downmix(interleaved: &[f64], channels: usize) -> Vec<f64> {
+    if channels <= 1 {
+        return interleaved.to_vec();
+    }
+
+    interleaved.chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f64>() / channels as f64)
+        .collect()
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < f64::EPSILON {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downmix() {
+        let data = [1.0, 0.0, 0.5, 0.5, -1.0, 1.0];
+        assert_eq!(downmix(&data, 2), vec![0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_resample_sine() {
+        let freq = 220.0;
+        let samples = (0..48000).map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / 48000.0).sin()).collect();
+        let audio = Audio { samples, sample_rate: 48000 }.resample(16000);
+
+        assert_eq!(audio.sample_rate, 16000);
+        assert_eq!(audio.samples.len(), 16000);
+
+        // Away from the edges the resampled signal should match the analytic sine
+        for i in 1000..15000 {
+            let expected = (2.0 * std::f64::consts::PI * freq * i as f64 / 16000.0).sin();
+            assert!((audio.samples[i] - expected).abs() < 1e-2, "Sample {} is {}, expected {}", i, audio.samples[i], expected);
+        }
+    }
+
+    #[test]
+    fn test_load_wav_stereo_16bit() {
+        let path = std::env::temp_dir().join("openvb_test_load_wav_stereo_16bit.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int
+        };
+
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(i16::MAX).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let audio = Audio::load(&path, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(audio.sample_rate, 44100);
+        assert_eq!(audio.samples.len(), 100);
+        assert!((audio.samples[0] - 0.5).abs() < 1e-3);
+    }
+}
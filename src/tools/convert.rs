@@ -0,0 +1,284 @@
+// Phoneset conversion through a shared registry. Every phoneset is mapped to and from X-SAMPA, the
+// format labels are stored in, so converting between two phonesets pivots through it.
+
+use std::{collections::{BTreeMap, HashMap}, path::Path, sync::{Arc, OnceLock, RwLock}};
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{encode::PhonemeEncoder, library::Library, tools::Phoneset, utterance::{FileDescriptor, Utterance}, Singer};
+
+static REGISTRY: OnceLock<RwLock<PhonesetRegistry>> = OnceLock::new();
+
+pub trait PhonemeMapping: Send + Sync {
+    // Returns None for symbols the phoneset has no X-SAMPA equivalent for
+    fn to_xsampa(&self, symbol: &str) -> Option<String>;
+    fn xsampa_to_native(&self, symbol: &str) -> Option<String>;
+
+    // Called before a label's symbols are converted out of this phoneset, and after they are converted into it.
+    // Phonesets that carry information X-SAMPA has no symbol for can stash and restore it here.
+    fn before_decode(&self, _utterance: &mut Utterance) {}
+    fn after_encode(&self, _utterance: &mut Utterance) {}
+
+    // Whether converting this symbol as a label's `prev` or `next` loses information `before_decode` only keeps
+    // for `curr`, such as ARPAbet stress
+    fn is_lossy(&self, _symbol: &str) -> bool {
+        false
+    }
+}
+
+pub struct XSampaMapping;
+
+impl PhonemeMapping for XSampaMapping {
+    fn to_xsampa(&self, symbol: &str) -> Option<String> {
+        Some(symbol.to_string())
+    }
+
+    fn xsampa_to_native(&self, symbol: &str) -> Option<String> {
+        Some(symbol.to_string())
+    }
+}
+
+// User-defined phoneset, stored as `[symbol, xsampa]` pairs. When several symbols share an X-SAMPA
+// equivalent, the first one is used for the reverse direction.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MappingTable {
+    pub symbols: Vec<(String, String)>
+}
+
+impl MappingTable {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+}
+
+impl PhonemeMapping for MappingTable {
+    fn to_xsampa(&self, symbol: &str) -> Option<String> {
+        self.symbols.iter().find(|(s, _)| s == symbol).map(|(_, x)| x.clone())
+    }
+
+    fn xsampa_to_native(&self, symbol: &str) -> Option<String> {
+        self.symbols.iter().find(|(_, x)| x == symbol).map(|(s, _)| s.clone())
+    }
+}
+
+pub struct PhonesetRegistry {
+    mappings: HashMap<Phoneset, Arc<dyn PhonemeMapping>>
+}
+
+impl Default for PhonesetRegistry {
+    fn default() -> Self {
+        let mut registry = PhonesetRegistry {
+            mappings: HashMap::new()
+        };
+
+        registry.register(Phoneset::XSampa, XSampaMapping);
+
+        #[cfg(feature = "translate")]
+        {
+            registry.register(Phoneset::IPA, crate::tools::ipa::IpaMapping);
+            registry.register(Phoneset::Arpa, crate::tools::arpa::ArpaMapping);
+            registry.register(Phoneset::CZampa, crate::tools::czampa::CZampaMapping);
+        }
+
+        registry
+    }
+}
+
+impl PhonesetRegistry {
+    // The registry used by `Convert::convert`, initialized with the built-in phonesets
+    pub fn global() -> &'static RwLock<PhonesetRegistry> {
+        REGISTRY.get_or_init(|| RwLock::new(PhonesetRegistry::default()))
+    }
+
+    pub fn register<M: PhonemeMapping + 'static>(&mut self, phoneset: Phoneset, mapping: M) {
+        self.mappings.insert(phoneset, Arc::new(mapping));
+    }
+
+    // Registers a JSON mapping table as `Phoneset::Custom(name)`
+    pub fn register_json<P: AsRef<Path>>(&mut self, name: &str, path: P) -> Result<Phoneset> {
+        let phoneset = Phoneset::Custom(name.to_string());
+        self.register(phoneset.clone(), MappingTable::load(path)?);
+
+        Ok(phoneset)
+    }
+
+    pub fn contains(&self, phoneset: &Phoneset) -> bool {
+        self.mappings.contains_key(phoneset)
+    }
+
+    pub fn converter(&self, from: &Phoneset, to: &Phoneset) -> Result<Converter> {
+        let get = |p: &Phoneset| self.mappings.get(p).cloned().ok_or(anyhow!("Phoneset is not registered: {:?}", p));
+
+        Ok(Converter {
+            from: from.clone(),
+            to: to.clone(),
+            from_mapping: get(from)?,
+            to_mapping: get(to)?,
+            report: ConversionReport::default()
+        })
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ConversionReport {
+    // Symbols that could not be converted, with the number of times they were encountered. They are left unchanged.
+    pub unmapped: BTreeMap<String, usize>,
+    // Context symbols that were converted but lost information on the way, counted the same way
+    pub lossy: BTreeMap<String, usize>
+}
+
+impl ConversionReport {
+    pub fn is_lossless(&self) -> bool {
+        self.unmapped.is_empty() && self.lossy.is_empty()
+    }
+}
+
+pub struct Converter {
+    pub from: Phoneset,
+    pub to: Phoneset,
+    from_mapping: Arc<dyn PhonemeMapping>,
+    to_mapping: Arc<dyn PhonemeMapping>,
+    pub report: ConversionReport
+}
+
+impl Converter {
+    pub fn convert_utterance(&mut self, utterance: &mut Utterance) {
+        self.from_mapping.before_decode(utterance);
+
+        for symbol in [&utterance.prev, &utterance.next] {
+            if self.from_mapping.is_lossy(symbol) {
+                *self.report.lossy.entry(symbol.clone()).or_insert(0) += 1;
+            }
+        }
+
+        utterance.prev = self.convert_symbol(&utterance.prev);
+        utterance.curr = self.convert_symbol(&utterance.curr);
+        utterance.next = self.convert_symbol(&utterance.next);
+
+        self.to_mapping.after_encode(utterance);
+    }
+
+    pub fn convert_symbol(&mut self, symbol: &str) -> String {
+        if PhonemeEncoder::is_silence(symbol) {
+            return symbol.to_string();
+        }
+
+        match self.from_mapping.to_xsampa(symbol).and_then(|x| self.to_mapping.xsampa_to_native(&x)) {
+            Some(converted) => converted,
+            None => {
+                *self.report.unmapped.entry(symbol.to_string()).or_insert(0) += 1;
+                symbol.to_string()
+            }
+        }
+    }
+}
+
+pub trait Convert {
+    fn convert_with(&mut self, converter: &mut Converter) -> Result<()>;
+
+    fn convert(&mut self, from: Phoneset, to: Phoneset) -> Result<ConversionReport> {
+        let mut converter = PhonesetRegistry::global().read().unwrap().converter(&from, &to)?;
+        self.convert_with(&mut converter)?;
+
+        Ok(converter.report)
+    }
+}
+
+impl Convert for Singer {
+    fn convert_with(&mut self, converter: &mut Converter) -> Result<()> {
+        // Check every library up front so a mismatch leaves the singer untouched
+        if let Some(library) = self.libraries.iter().find(|l| l.phoneset != converter.from) {
+            bail!("Library {} is written in {:?}, not {:?}", library.name, library.phoneset, converter.from);
+        }
+
+        for library in self.libraries.iter_mut() {
+            library.convert_with(converter)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Convert for Library {
+    fn convert_with(&mut self, converter: &mut Converter) -> Result<()> {
+        if self.phoneset != converter.from {
+            bail!("Library {} is written in {:?}, not {:?}", self.name, self.phoneset, converter.from);
+        }
+
+        for file in self.files.iter_mut() {
+            file.convert_with(converter)?;
+        }
+
+        self.phoneset = converter.to.clone();
+
+        Ok(())
+    }
+}
+
+// Files and labels do not record their phoneset, so converting them directly is not checked
+impl Convert for FileDescriptor {
+    fn convert_with(&mut self, converter: &mut Converter) -> Result<()> {
+        for label in self.labels.iter_mut() {
+            label.convert_with(converter)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Convert for Utterance {
+    fn convert_with(&mut self, converter: &mut Converter) -> Result<()> {
+        converter.convert_utterance(self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_table() {
+        let table: MappingTable = serde_json::from_str(r#"[["aa", "a:"], ["sh", "S"]]"#).unwrap();
+
+        let mut registry = PhonesetRegistry::default();
+        registry.register(Phoneset::Custom(String::from("test")), table);
+
+        let mut utterance = Utterance {
+            prev: String::from("sil"),
+            curr: String::from("sh"),
+            next: String::from("aa"),
+            ..Default::default()
+        };
+
+        let mut converter = registry.converter(&Phoneset::Custom(String::from("test")), &Phoneset::XSampa).unwrap();
+        utterance.convert_with(&mut converter).unwrap();
+        assert_eq!((utterance.prev.as_str(), utterance.curr.as_str(), utterance.next.as_str()), ("sil", "S", "a:"));
+        assert!(converter.report.is_lossless());
+
+        let mut converter = registry.converter(&Phoneset::XSampa, &Phoneset::Custom(String::from("test"))).unwrap();
+        utterance.curr = String::from("x");
+        utterance.convert_with(&mut converter).unwrap();
+        assert_eq!((utterance.curr.as_str(), utterance.next.as_str()), ("x", "aa"));
+        assert_eq!(converter.report.unmapped.get("x"), Some(&1));
+    }
+
+    #[test]
+    fn test_refuse_double_conversion() {
+        let mut library = Library::default();
+        library.convert(Phoneset::XSampa, Phoneset::XSampa).unwrap();
+        assert_eq!(library.phoneset, Phoneset::XSampa);
+
+        library.phoneset = Phoneset::IPA;
+        assert!(library.convert(Phoneset::XSampa, Phoneset::IPA).is_err());
+    }
+
+    #[test]
+    fn test_unregistered_phoneset() {
+        let registry = PhonesetRegistry::default();
+        assert!(registry.converter(&Phoneset::Custom(String::from("missing")), &Phoneset::XSampa).is_err());
+    }
+}
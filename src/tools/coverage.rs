@@ -0,0 +1,319 @@
+// Phoneme coverage of a library: how often each monophone, diphone and triphone was recorded, compared
+// against the units a target inventory or lyrics corpus needs.
+
+use std::{collections::{BTreeMap, BTreeSet}, fmt::{self, Display, Write}};
+
+use anyhow::Result;
+
+use crate::{encode::PhonemeEncoder, library::Library, tools::{dictionary::Lexicon, Phoneset}, Singer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnitKind {
+    Monophone,
+    // curr + next, as in `Context::Diphone`
+    Diphone,
+    Triphone
+}
+
+impl Display for UnitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnitKind::Monophone => write!(f, "monophone"),
+            UnitKind::Diphone => write!(f, "diphone"),
+            UnitKind::Triphone => write!(f, "triphone")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unit {
+    pub kind: UnitKind,
+    pub phonemes: Vec<String>
+}
+
+impl Unit {
+    pub fn new(kind: UnitKind, phonemes: &[&str]) -> Self {
+        Unit {
+            kind,
+            phonemes: phonemes.iter().map(|p| p.to_string()).collect()
+        }
+    }
+
+    // The units a label with this context contributes. Silence is only counted as a neighbour.
+    pub fn from_context(prev: &str, curr: &str, next: &str) -> Vec<Unit> {
+        if PhonemeEncoder::is_silence(curr) {
+            return vec![];
+        }
+
+        vec![
+            Unit::new(UnitKind::Monophone, &[curr]),
+            Unit::new(UnitKind::Diphone, &[curr, next]),
+            Unit::new(UnitKind::Triphone, &[prev, curr, next])
+        ]
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.phonemes.join(" "))
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Coverage {
+    pub library: String,
+    pub counts: BTreeMap<Unit, usize>
+}
+
+impl Coverage {
+    pub fn from_library(library: &Library) -> Self {
+        let mut counts: BTreeMap<Unit, usize> = BTreeMap::new();
+        for label in library.iter_labels() {
+            for unit in Unit::from_context(&label.prev, &label.curr, &label.next) {
+                *counts.entry(unit).or_insert(0) += 1;
+            }
+        }
+
+        Coverage {
+            library: library.name.clone(),
+            counts
+        }
+    }
+
+    // Counts of every library combined, under the singer's name
+    pub fn from_singer(singer: &Singer) -> Self {
+        let mut coverage = Coverage {
+            library: singer.meta.name.clone(),
+            counts: BTreeMap::new()
+        };
+
+        for library in singer.libraries.iter() {
+            for (unit, count) in Self::from_library(library).counts {
+                *coverage.counts.entry(unit).or_insert(0) += count;
+            }
+        }
+
+        coverage
+    }
+
+    pub fn count(&self, unit: &Unit) -> usize {
+        self.counts.get(unit).copied().unwrap_or(0)
+    }
+
+    pub fn units(&self, kind: UnitKind) -> impl Iterator<Item = (&Unit, &usize)> {
+        self.counts.iter().filter(move |(u, _)| u.kind == kind)
+    }
+
+    // Every target unit with its count. Units recorded fewer than `min_count` times are under-represented.
+    pub fn compare(&self, target: &CoverageTarget, min_count: usize) -> CoverageReport {
+        let entries = target.units.iter().map(|unit| {
+            let count = self.count(unit);
+            let status = match count {
+                0 => CoverageStatus::Missing,
+                c if c < min_count => CoverageStatus::UnderRepresented,
+                _ => CoverageStatus::Covered
+            };
+
+            UnitCoverage {
+                unit: unit.clone(),
+                count,
+                status
+            }
+        }).collect();
+
+        CoverageReport {
+            library: self.library.clone(),
+            min_count,
+            entries
+        }
+    }
+}
+
+// The units a library is expected to cover
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct CoverageTarget {
+    pub units: BTreeSet<Unit>
+}
+
+impl CoverageTarget {
+    // Every phoneme of the inventory, and every diphone between them or into silence
+    pub fn from_inventory<S: AsRef<str>>(phonemes: &[S]) -> Self {
+        let mut units = BTreeSet::new();
+
+        for curr in phonemes.iter().map(|p| p.as_ref()) {
+            units.insert(Unit::new(UnitKind::Monophone, &[curr]));
+            units.insert(Unit::new(UnitKind::Diphone, &[curr, "sil"]));
+            for next in phonemes.iter() {
+                units.insert(Unit::new(UnitKind::Diphone, &[curr, next.as_ref()]));
+            }
+        }
+
+        CoverageTarget {
+            units
+        }
+    }
+
+    // Every unit that occurs when singing the lyrics, one phrase per line with silence around it
+    pub fn from_lyrics(lexicon: &Lexicon, language: &str, lyrics: &str, phoneset: &Phoneset) -> Result<Self> {
+        let mut units = BTreeSet::new();
+
+        for line in lyrics.lines() {
+            let transcription = lexicon.transcribe(language, line, phoneset)?;
+            units.extend(Self::sequence_units(&transcription.phonemes));
+        }
+
+        Ok(CoverageTarget {
+            units
+        })
+    }
+
+    fn sequence_units(phonemes: &[String]) -> Vec<Unit> {
+        let padded = std::iter::once("sil")
+            .chain(phonemes.iter().map(|p| p.as_str()))
+            .chain(std::iter::once("sil"))
+            .collect::<Vec<&str>>();
+
+        padded.windows(3).flat_map(|w| Unit::from_context(w[0], w[1], w[2])).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageStatus {
+    Covered,
+    UnderRepresented,
+    Missing
+}
+
+impl Display for CoverageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoverageStatus::Covered => write!(f, "covered"),
+            CoverageStatus::UnderRepresented => write!(f, "under-represented"),
+            CoverageStatus::Missing => write!(f, "missing")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitCoverage {
+    pub unit: Unit,
+    pub count: usize,
+    pub status: CoverageStatus
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    pub library: String,
+    pub min_count: usize,
+    pub entries: Vec<UnitCoverage>
+}
+
+impl CoverageReport {
+    pub fn missing(&self) -> impl Iterator<Item = &UnitCoverage> {
+        self.entries.iter().filter(|e| e.status == CoverageStatus::Missing)
+    }
+
+    pub fn under_represented(&self) -> impl Iterator<Item = &UnitCoverage> {
+        self.entries.iter().filter(|e| e.status == CoverageStatus::UnderRepresented)
+    }
+
+    // Share of the target units of this kind that were recorded at least once, or None if the target has none
+    pub fn ratio(&self, kind: UnitKind) -> Option<f64> {
+        let units = self.entries.iter().filter(|e| e.unit.kind == kind).collect::<Vec<_>>();
+        if units.is_empty() {
+            return None;
+        }
+
+        let covered = units.iter().filter(|e| e.status != CoverageStatus::Missing).count();
+        Some(covered as f64 / units.len() as f64)
+    }
+
+    // A summary per unit kind followed by every missing or under-represented unit
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "# Coverage: {}\n", self.library).unwrap();
+        writeln!(out, "| Kind | Covered | Target |\n| --- | --- | --- |").unwrap();
+        for kind in [UnitKind::Monophone, UnitKind::Diphone, UnitKind::Triphone] {
+            let units = self.entries.iter().filter(|e| e.unit.kind == kind);
+            let (target, covered) = units.fold((0, 0), |(t, c), e| (t + 1, c + (e.status != CoverageStatus::Missing) as usize));
+            if target > 0 {
+                writeln!(out, "| {} | {} | {} |", kind, covered, target).unwrap();
+            }
+        }
+
+        let gaps = self.entries.iter().filter(|e| e.status != CoverageStatus::Covered).collect::<Vec<_>>();
+        if !gaps.is_empty() {
+            writeln!(out, "\n| Kind | Unit | Count | Status |\n| --- | --- | --- | --- |").unwrap();
+            for entry in gaps {
+                let unit = entry.unit.to_string().replace('|', "\\|");
+                writeln!(out, "| {} | `{}` | {} | {} |", entry.unit.kind, unit, entry.count, entry.status).unwrap();
+            }
+        }
+
+        out
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("kind,unit,count,status\n");
+
+        for entry in self.entries.iter() {
+            writeln!(out, "{},{},{},{}", entry.unit.kind, csv_field(&entry.unit.to_string()), entry.count, entry.status).unwrap();
+        }
+
+        out
+    }
+}
+
+// X-SAMPA uses `"` for primary stress, so units may need quoting
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tools::dictionary::PronunciationDictionary, utterance::{FileDescriptor, Utterance}};
+
+    #[test]
+    fn test_coverage_report() {
+        let labels = [("sil", "k", "a"), ("k", "a", "sil"), ("sil", "a", "sil")].iter().map(|(p, c, n)| Utterance {
+            prev: p.to_string(),
+            curr: c.to_string(),
+            next: n.to_string(),
+            ..Default::default()
+        }).collect();
+
+        let library = Library {
+            name: String::from("Test"),
+            files: vec![FileDescriptor {
+                labels,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let coverage = Coverage::from_library(&library);
+        assert_eq!(coverage.units(UnitKind::Monophone).count(), 2);
+        assert_eq!(coverage.count(&Unit::from_context("k", "a", "sil")[1]), 2);
+
+        let report = coverage.compare(&CoverageTarget::from_inventory(&["k", "a"]), 2);
+        assert_eq!(report.ratio(UnitKind::Monophone), Some(1.0));
+        assert_eq!(report.under_represented().map(|e| e.unit.to_string()).collect::<Vec<_>>(), vec!["k", "k a"]);
+        assert_eq!(report.missing().count(), 4);
+
+        let mut lexicon = Lexicon::default();
+        lexicon.insert("ja", PronunciationDictionary::parse("ka k a\naka a k a", Phoneset::XSampa));
+        let target = CoverageTarget::from_lyrics(&lexicon, "ja", "ka\naka", &Phoneset::XSampa).unwrap();
+
+        let report = coverage.compare(&target, 1);
+        assert_eq!(report.missing().map(|e| e.unit.to_string()).collect::<Vec<_>>(), vec!["a k", "a k a", "sil a k"]);
+        assert!(report.to_markdown().contains("| triphone | `sil a k` | 0 | missing |"));
+        assert!(report.to_csv().contains("diphone,a k,0,missing\n"));
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+
+use crate::tools::{convert::{ConversionReport, Convert, PhonemeMapping}, Phoneset};
+
+// CZampa (Czech SAMPA, as used by our Czech and Slovak banks) to X-SAMPA.
+// Every X-SAMPA symbol in this table is unique, so the conversion is lossless in both directions.
+pub(crate) const CZAMPA_DATA: [(&str, &str); 48] = [
+    // Vowels and diphthongs
+    ("a", "a"), ("a:", "a:"), ("e", "e"), ("e:", "e:"), ("i", "I"), ("i:", "i:"), ("o", "o"),
+    ("o:", "o:"), ("u", "u"), ("u:", "u:"), ("@", "@"), ("o_u", "oU"), ("a_u", "aU"), ("e_u", "eU"),
+
+    // Plosives and affricates
+    ("p", "p"), ("b", "b"), ("t", "t"), ("d", "d"), ("c", "c"), ("J\\", "J\\"), ("k", "k"),
+    ("g", "g"), ("?", "?"), ("ts", "ts"), ("dz", "dz"), ("tS", "tS"), ("dZ", "dZ"),
+
+    // Fricatives, including the raised alveolar trill (ř)
+    ("f", "f"), ("v", "v"), ("s", "s"), ("z", "z"), ("S", "S"), ("Z", "Z"), ("x", "x"),
+    ("h\\", "h\\"), ("P\\", "r_r"), ("Q\\", "r_r_0"),
+
+    // Sonorants
+    ("m", "m"), ("F", "F"), ("n", "n"), ("N", "N"), ("J", "J"), ("r", "r"), ("l", "l"),
+    ("j", "j"), ("r=", "r="), ("l=", "l="), ("m=", "m=")
+];
+
+pub trait FromCZampa {
+    fn from_czampa(&mut self) -> Result<ConversionReport>;
+}
+
+pub trait ToCZampa {
+    fn to_czampa(&mut self) -> Result<ConversionReport>;
+}
+
+impl<T: Convert> FromCZampa for T {
+    fn from_czampa(&mut self) -> Result<ConversionReport> {
+        self.convert(Phoneset::CZampa, Phoneset::XSampa)
+    }
+}
+
+impl<T: Convert> ToCZampa for T {
+    fn to_czampa(&mut self) -> Result<ConversionReport> {
+        self.convert(Phoneset::XSampa, Phoneset::CZampa)
+    }
+}
+
+pub struct CZampaMapping;
+
+impl PhonemeMapping for CZampaMapping {
+    fn to_xsampa(&self, symbol: &str) -> Option<String> {
+        CZAMPA_DATA.iter().find(|(c, _)| *c == symbol).map(|(_, x)| x.to_string())
+    }
+
+    fn xsampa_to_native(&self, symbol: &str) -> Option<String> {
+        CZAMPA_DATA.iter().find(|(_, x)| *x == symbol).map(|(c, _)| c.to_string())
+    }
+}
+
+// Converts a single CZampa phoneme to X-SAMPA. Unknown symbols are returned unchanged.
+pub fn czampa_to_xsampa(symbol: &str) -> String {
+    CZampaMapping.to_xsampa(symbol).unwrap_or(symbol.to_string())
+}
+
+// Converts a single X-SAMPA phoneme to CZampa. Unknown symbols are returned unchanged.
+pub fn xsampa_to_czampa(symbol: &str) -> String {
+    CZampaMapping.xsampa_to_native(symbol).unwrap_or(symbol.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utterance::Utterance;
+
+    #[test]
+    fn test_czampa_roundtrip() {
+        for (czampa, xsampa) in CZAMPA_DATA.iter() {
+            assert_eq!(czampa_to_xsampa(czampa), *xsampa);
+            assert_eq!(xsampa_to_czampa(xsampa), *czampa);
+        }
+    }
+
+    #[test]
+    fn test_utterance_roundtrip() {
+        let original = Utterance {
+            prev: String::from("sil"),
+            curr: String::from("P\\"),
+            next: String::from("i:"),
+            ..Default::default()
+        };
+
+        let mut utterance = original.clone();
+        utterance.from_czampa().unwrap();
+        assert_eq!((utterance.prev.as_str(), utterance.curr.as_str(), utterance.next.as_str()), ("sil", "r_r", "i:"));
+
+        utterance.to_czampa().unwrap();
+        assert_eq!(utterance, original);
+    }
+}
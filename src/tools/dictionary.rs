@@ -0,0 +1,200 @@
+// Pronunciation dictionaries for turning lyrics into phoneme sequences. Both CMUdict files
+// (`WORD(1)  P1 P2`, `;;;` comments) and plain `word phoneme phoneme` files are supported.
+
+use std::{collections::{HashMap, HashSet}, path::Path};
+
+use anyhow::{Result, anyhow};
+
+use crate::{encode::PhonemeEncoder, library::Library, tools::{convert::PhonesetRegistry, Phoneset}};
+
+#[derive(Default, Debug, Clone)]
+pub struct PronunciationDictionary {
+    // Phoneset the pronunciations are written in
+    pub phoneset: Phoneset,
+    // Lowercase word to its pronunciations, in file order
+    pub entries: HashMap<String, Vec<Vec<String>>>
+}
+
+impl PronunciationDictionary {
+    pub fn load<P: AsRef<Path>>(path: P, phoneset: Phoneset) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&data, phoneset))
+    }
+
+    pub fn parse(data: &str, phoneset: Phoneset) -> Self {
+        let mut entries: HashMap<String, Vec<Vec<String>>> = HashMap::new();
+
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(";;;") || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let Some(word) = parts.next() else { continue };
+            let phonemes = parts.map(|p| p.to_string()).collect::<Vec<String>>();
+            if phonemes.is_empty() {
+                continue;
+            }
+
+            // CMUdict marks alternative pronunciations as WORD(1), WORD(2), ...
+            let word = match word.split_once('(') {
+                Some((w, variant)) if variant.ends_with(')') => w,
+                _ => word
+            };
+
+            entries.entry(word.to_lowercase()).or_default().push(phonemes);
+        }
+
+        PronunciationDictionary {
+            phoneset,
+            entries
+        }
+    }
+
+    pub fn lookup(&self, word: &str) -> Option<&Vec<Vec<String>>> {
+        self.entries.get(&word.to_lowercase())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Transcription {
+    // Phonemes of every known word, in the requested phoneset
+    pub phonemes: Vec<String>,
+    pub unknown_words: Vec<String>,
+    // Phonemes the library has no label for
+    pub missing: Vec<String>
+}
+
+// Pronunciation dictionaries keyed by language code (`Language::default`, `FileDescriptor::language`)
+#[derive(Default, Debug, Clone)]
+pub struct Lexicon {
+    pub dictionaries: HashMap<String, PronunciationDictionary>
+}
+
+impl Lexicon {
+    pub fn insert(&mut self, language: &str, dictionary: PronunciationDictionary) {
+        self.dictionaries.insert(language.to_string(), dictionary);
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, language: &str, path: P, phoneset: Phoneset) -> Result<()> {
+        self.insert(language, PronunciationDictionary::load(path, phoneset)?);
+        Ok(())
+    }
+
+    // Transcribes a lyric using the first pronunciation of each word, converted to `phoneset`
+    pub fn transcribe(&self, language: &str, lyric: &str, phoneset: &Phoneset) -> Result<Transcription> {
+        let dictionary = self.dictionaries.get(language).ok_or(anyhow!("No dictionary for language: {}", language))?;
+        let mut converter = PhonesetRegistry::global().read().unwrap().converter(&dictionary.phoneset, phoneset)?;
+
+        let mut transcription = Transcription::default();
+        for word in lyric.split_whitespace() {
+            match dictionary.lookup(word).and_then(|p| p.first()) {
+                Some(pronunciation) => transcription.phonemes.extend(pronunciation.iter().map(|p| converter.convert_symbol(p))),
+                None => transcription.unknown_words.push(word.to_string())
+            }
+        }
+
+        Ok(transcription)
+    }
+
+    // Transcribes a lyric in the library's language and phoneset and lists the phonemes it has no label for
+    pub fn check(&self, library: &Library, lyric: &str) -> Result<Transcription> {
+        let language = library.language.as_ref().map(|l| l.default.as_str()).ok_or(anyhow!("Library {} has no language", library.name))?;
+        let mut transcription = self.transcribe(language, lyric, &library.phoneset)?;
+
+        let available = library.iter_labels().map(|l| l.curr.as_str()).collect::<HashSet<&str>>();
+        for phoneme in transcription.phonemes.iter() {
+            if !available.contains(phoneme.as_str()) && !transcription.missing.contains(phoneme) {
+                transcription.missing.push(phoneme.clone());
+            }
+        }
+
+        Ok(transcription)
+    }
+
+    // Adds every dictionary word whose pronunciation matches a file's phonemes (silence excluded) to its aliases
+    pub fn fill_aliases(&self, library: &mut Library) -> Result<()> {
+        let language = library.language.as_ref().map(|l| l.default.clone()).ok_or(anyhow!("Library {} has no language", library.name))?;
+        let dictionary = self.dictionaries.get(&language).ok_or(anyhow!("No dictionary for language: {}", language))?;
+        let mut converter = PhonesetRegistry::global().read().unwrap().converter(&dictionary.phoneset, &library.phoneset)?;
+
+        // Words are visited in order so homophones always end up in the same alias order
+        let mut entries = dictionary.entries.iter().collect::<Vec<_>>();
+        entries.sort();
+
+        let mut words: HashMap<Vec<String>, Vec<&String>> = HashMap::new();
+        for (word, pronunciations) in entries {
+            for pronunciation in pronunciations {
+                let phonemes = pronunciation.iter().map(|p| converter.convert_symbol(p)).collect();
+                words.entry(phonemes).or_default().push(word);
+            }
+        }
+
+        for file in library.files.iter_mut() {
+            let phonemes = file.labels.iter()
+                .filter(|l| !PhonemeEncoder::is_silence(&l.curr))
+                .map(|l| l.curr.clone())
+                .collect::<Vec<String>>();
+
+            for word in words.get(&phonemes).into_iter().flatten() {
+                if !file.aliases.contains(word) {
+                    file.aliases.push(word.to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{singer::Language, utterance::{FileDescriptor, Utterance}};
+
+    #[test]
+    fn test_parse_cmudict() {
+        let data = ";;; comment\nTOMATO  T AH0 M EY1 T OW2\nTOMATO(1)  T AH0 M AA1 T OW2\n";
+        let dictionary = PronunciationDictionary::parse(data, Phoneset::Arpa);
+
+        let tomato = dictionary.lookup("Tomato").unwrap();
+        assert_eq!(tomato.len(), 2);
+        assert_eq!(tomato[1][3], "AA1");
+    }
+
+    #[test]
+    fn test_check_library() {
+        let mut lexicon = Lexicon::default();
+        lexicon.insert("ja", PronunciationDictionary::parse("sakura s a k M 4 a\nhana h a n a", Phoneset::XSampa));
+
+        let labels = ["s", "a", "k", "4"].iter().map(|c| Utterance {
+            curr: c.to_string(),
+            ..Default::default()
+        }).collect();
+
+        let mut library = Library {
+            name: String::from("Test"),
+            language: Some(Language {
+                default: String::from("ja"),
+                supported: vec![]
+            }),
+            files: vec![FileDescriptor {
+                labels,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let transcription = lexicon.check(&library, "sakura sakura yayoi").unwrap();
+        assert_eq!(transcription.phonemes.len(), 12);
+        assert_eq!(transcription.unknown_words, vec!["yayoi"]);
+        assert_eq!(transcription.missing, vec!["M"]);
+
+        library.files[0].labels.truncate(2);
+        lexicon.insert("ja", PronunciationDictionary::parse("za z a\nsa s a\nsah s a\nsaa s a", Phoneset::XSampa));
+        lexicon.fill_aliases(&mut library).unwrap();
+        assert_eq!(library.files[0].aliases, vec!["sa", "saa", "sah"]);
+    }
+}
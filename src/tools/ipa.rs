@@ -1,87 +1,219 @@
+use std::collections::HashMap;
+
 use ipa_translate::{ipa_to_xsampa, xsampa_to_ipa};
+use anyhow::Result;
+
+use crate::{tools::{convert::{ConversionReport, Convert, PhonemeMapping}, Phoneset}, utterance::Utterance};
+
+const TIE_BARS: [char; 2] = ['\u{0361}', '\u{035C}'];
+
+const LENGTH_MARKS: [(char, &str); 2] = [('ː', ":"), ('ˑ', ":\\")];
+
+// Chao tone letters and superscript tone numbers, both stored as their digit (5 = highest)
+const TONE_LETTERS: [(char, char); 5] = [('˥', '5'), ('˦', '4'), ('˧', '3'), ('˨', '2'), ('˩', '1')];
+const TONE_NUMBERS: [(char, char); 5] = [('⁵', '5'), ('⁴', '4'), ('³', '3'), ('²', '2'), ('¹', '1')];
+
+// X-SAMPA affricates, split into the two halves joined by a tie bar in IPA
+const AFFRICATES: [(&str, &str, &str); 10] = [
+    ("ts", "t", "s"), ("dz", "d", "z"), ("tS", "t", "S"), ("dZ", "d", "Z"), ("ts\\", "t", "s\\"),
+    ("dz\\", "d", "z\\"), ("tK", "t", "K"), ("pf", "p", "f"), ("kx", "k", "x"), ("t`s`", "t`", "s`")
+];
 
-use crate::{encode::PhonemeEncoder, library::Library, utterance::{FileDescriptor, Utterance}, Singer};
+// Keys in `Utterance::extras` holding the Chao tone digits (e.g. 214) of the label's own phoneme and its neighbours
+pub const TONE_KEY: &str = "tone";
+pub const PREV_TONE_KEY: &str = "prev_tone";
+pub const NEXT_TONE_KEY: &str = "next_tone";
 
 pub trait FromIPA {
-    fn from_ipa(&mut self);
+    fn from_ipa(&mut self) -> Result<ConversionReport>;
 }
 
 pub trait ToIPA {
-    fn to_ipa(&mut self);
+    fn to_ipa(&mut self) -> Result<ConversionReport>;
 }
 
-impl FromIPA for Singer {
-    fn from_ipa(&mut self) {
-        for library in self.libraries.iter_mut() {
-            library.from_ipa();
-        }
+impl<T: Convert> FromIPA for T {
+    fn from_ipa(&mut self) -> Result<ConversionReport> {
+        self.convert(Phoneset::IPA, Phoneset::XSampa)
     }
 }
 
-impl FromIPA for Library {
-    fn from_ipa(&mut self) {
-        for file in self.files.iter_mut() {
-            file.from_ipa();
-        }
+impl<T: Convert> ToIPA for T {
+    fn to_ipa(&mut self) -> Result<ConversionReport> {
+        self.convert(Phoneset::XSampa, Phoneset::IPA)
     }
 }
 
-impl FromIPA for FileDescriptor {
-    fn from_ipa(&mut self) {
-        for label in self.labels.iter_mut() {
-            label.from_ipa();
+// A tokenized IPA label. Each segment is one base character with its diacritics, modifier letters and length marks;
+// characters joined by a tie bar share a segment, without the tie bar. Tone marks are collected separately.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct IpaLabel {
+    pub segments: Vec<String>,
+    pub tone: Option<String>
+}
+
+pub fn tokenize_ipa(label: &str) -> IpaLabel {
+    let mut segments: Vec<String> = vec![];
+    let mut tone = String::new();
+    let mut tied = false;
+
+    for c in label.chars() {
+        if let Some(digit) = tone_digit(c) {
+            tone.push(digit);
+            continue;
         }
+
+        if TIE_BARS.contains(&c) {
+            tied = true;
+            continue;
+        }
+
+        match segments.last_mut() {
+            Some(segment) if tied || is_modifier(c) => segment.push(c),
+            _ => segments.push(c.to_string())
+        }
+
+        tied = false;
+    }
+
+    IpaLabel {
+        segments,
+        tone: Some(tone).filter(|t| !t.is_empty())
     }
 }
 
-impl FromIPA for Utterance {
-    fn from_ipa(&mut self) {
-        if !PhonemeEncoder::is_silence(&self.prev) {
-            self.prev = ipa_to_xsampa(&self.prev);
+pub struct IpaMapping;
+
+impl PhonemeMapping for IpaMapping {
+    // Only converts labels whose every segment has an X-SAMPA equivalent; anything else is left for the report.
+    // Tone marks are not part of the X-SAMPA symbol: `before_decode` keeps them in the label's extras.
+    fn to_xsampa(&self, symbol: &str) -> Option<String> {
+        tokenize_ipa(symbol).segments.iter()
+            .map(|s| segment_to_xsampa(s))
+            .collect::<Option<Vec<String>>>()
+            .map(|s| s.concat())
+    }
+
+    fn xsampa_to_native(&self, symbol: &str) -> Option<String> {
+        let (core, length) = match symbol.strip_suffix(":\\") {
+            Some(core) => (core, "ˑ"),
+            None => match symbol.strip_suffix(':') {
+                Some(core) => (core, "ː"),
+                None => (symbol, "")
+            }
+        };
+
+        let ipa = match AFFRICATES.iter().find(|(a, _, _)| *a == core) {
+            Some((_, first, second)) => format!("{}{}{}", xsampa_to_ipa(first), TIE_BARS[0], xsampa_to_ipa(second)),
+            None => xsampa_to_ipa(core)
+        };
+
+        // IPA shares only the lowercase letters with X-SAMPA, so any other ASCII character was not converted
+        if !ipa.chars().all(|c| !c.is_ascii() || c.is_ascii_lowercase()) {
+            return None;
         }
-        
-        self.curr = ipa_to_xsampa(&self.curr);
 
-        if !PhonemeEncoder::is_silence(&self.next) {
-            self.next = ipa_to_xsampa(&self.next);
+        Some(ipa + length)
+    }
+
+    fn before_decode(&self, utterance: &mut Utterance) {
+        let symbols = [(PREV_TONE_KEY, &utterance.prev), (TONE_KEY, &utterance.curr), (NEXT_TONE_KEY, &utterance.next)];
+        let tones = symbols.map(|(key, symbol)| (key, tokenize_ipa(symbol).tone.and_then(|t| t.parse::<f32>().ok())));
+
+        for (key, tone) in tones {
+            if let Some(tone) = tone {
+                utterance.extras.get_or_insert_with(HashMap::new).insert(key.to_string(), tone);
+            }
         }
     }
-}
 
-impl ToIPA for Singer {
-    fn to_ipa(&mut self) {
-        for library in self.libraries.iter_mut() {
-            library.to_ipa();
+    fn after_encode(&self, utterance: &mut Utterance) {
+        if let Some(extras) = utterance.extras.as_mut() {
+            let symbols = [(PREV_TONE_KEY, &mut utterance.prev), (TONE_KEY, &mut utterance.curr), (NEXT_TONE_KEY, &mut utterance.next)];
+
+            for (key, symbol) in symbols {
+                if let Some(tone) = extras.remove(key) {
+                    push_tone(symbol, tone);
+                }
+            }
+        }
+
+        if utterance.extras.as_ref().is_some_and(|e| e.is_empty()) {
+            utterance.extras = None;
         }
     }
 }
 
-impl ToIPA for Library {
-    fn to_ipa(&mut self) {
-        for file in self.files.iter_mut() {
-            file.to_ipa();
+fn segment_to_xsampa(segment: &str) -> Option<String> {
+    let mut core = String::new();
+    let mut length = String::new();
+
+    for c in segment.chars() {
+        match LENGTH_MARKS.iter().find(|(m, _)| *m == c) {
+            Some((_, x)) => length.push_str(x),
+            None => core.push(c)
         }
     }
+
+    // X-SAMPA is pure ASCII, so anything left over is an IPA character without an equivalent
+    Some(ipa_to_xsampa(&core) + &length).filter(|x| x.is_ascii())
 }
 
-impl ToIPA for FileDescriptor {
-    fn to_ipa(&mut self) {
-        for label in self.labels.iter_mut() {
-            label.to_ipa();
+fn push_tone(symbol: &mut String, tone: f32) {
+    for digit in (tone as u32).to_string().chars() {
+        if let Some((letter, _)) = TONE_LETTERS.iter().find(|(_, d)| *d == digit) {
+            symbol.push(*letter);
         }
     }
 }
 
-impl ToIPA for Utterance {
-    fn to_ipa(&mut self) {
-        if !PhonemeEncoder::is_silence(&self.prev) {
-            self.prev = xsampa_to_ipa(&self.prev);
-        }
-        
-        self.curr = xsampa_to_ipa(&self.curr);
+fn tone_digit(c: char) -> Option<char> {
+    TONE_LETTERS.iter().chain(TONE_NUMBERS.iter()).find(|(t, _)| *t == c).map(|(_, d)| *d)
+}
 
-        if !PhonemeEncoder::is_silence(&self.next) {
-            self.next = xsampa_to_ipa(&self.next);
-        }
+// Combining diacritics and spacing modifier letters (ʰ, ʲ, ː, ˞, ...) belong to the preceding base character
+fn is_modifier(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{1DC0}'..='\u{1DFF}' | '\u{02B0}'..='\u{02FF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_ipa() {
+        let label = tokenize_ipa("t͡sʰaː˥˩");
+        assert_eq!(label.segments, vec!["tsʰ", "aː"]);
+        assert_eq!(label.tone.as_deref(), Some("51"));
+
+        let label = tokenize_ipa("ma²¹⁴");
+        assert_eq!(label.segments, vec!["m", "a"]);
+        assert_eq!(label.tone.as_deref(), Some("214"));
+    }
+
+    #[test]
+    fn test_unknown_xsampa() {
+        assert_eq!(IpaMapping.xsampa_to_native("a:").as_deref(), Some("aː"));
+        assert_eq!(IpaMapping.xsampa_to_native("#"), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tone_roundtrip() {
+        let mut utterance = Utterance {
+            prev: String::from("i˥"),
+            curr: String::from("a˨˩˦"),
+            next: String::from("sil"),
+            ..Default::default()
+        };
+
+        IpaMapping.before_decode(&mut utterance);
+        assert_eq!(utterance.extras.as_ref().and_then(|e| e.get(TONE_KEY)), Some(&214.0));
+        assert_eq!(utterance.extras.as_ref().and_then(|e| e.get(PREV_TONE_KEY)), Some(&5.0));
+
+        utterance.prev = String::from("i");
+        utterance.curr = String::from("a");
+        IpaMapping.after_encode(&mut utterance);
+        assert_eq!((utterance.prev.as_str(), utterance.curr.as_str(), utterance.next.as_str()), ("i˥", "a˨˩˦", "sil"));
+        assert!(utterance.extras.is_none());
+    }
+}
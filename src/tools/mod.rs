@@ -1,12 +1,23 @@
-enum Phoneset {
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Phoneset {
+    #[default]
     XSampa,
     IPA,
     Arpa,
-    CZampa
+    CZampa,
+    Custom(String)
 }
 
-#[cfg(feature = "generator")]
+pub mod convert;
+pub mod coverage;
+pub mod dictionary;
 pub mod pitch;
+pub mod reclist;
+
+#[cfg(feature = "audio")]
+pub mod audio;
 
 #[cfg(feature = "translate")]
 pub mod ipa;
@@ -14,4 +25,5 @@ pub mod ipa;
 #[cfg(feature = "translate")]
 pub mod arpa;
 
-mod scrape;
+#[cfg(feature = "translate")]
+pub mod czampa;
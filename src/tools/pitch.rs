@@ -1,75 +1,299 @@
 use std::path::Path;
 
-use anyhow::{Result, bail};
-use rsworld::{dio, stonemask};
-use rsworld_sys::DioOption;
-use wavers::{Samples, Wav};
+#[cfg(any(feature = "world", feature = "yin"))]
+use anyhow::{bail, Result};
+#[cfg(feature = "world")]
+use rsworld::{dio, harvest, stonemask};
+#[cfg(feature = "world")]
+use rsworld_sys::{DioOption, HarvestOption};
+use serde::{Deserialize, Serialize};
 
 
+#[cfg(feature = "generator-core")]
+use crate::{encode::PhonemeEncoder, tools::audio::Audio};
 use crate::utterance::Utterance;
 
-pub fn detect_pitch_from_samples(data: &Vec<f64>, sr: i32) -> f64 {
-    let dio_option = DioOption {
-        f0_floor: 71.0,
-        f0_ceil: 1760.0,
-        frame_period: 5.0,
-        channels_in_octave: 2.0,
-        speed: 1,
-        allowed_range: 0.1,
+// Frames whose cumulative mean normalized difference stays above this value are considered unvoiced
+#[cfg(feature = "yin")]
+const YIN_THRESHOLD: f64 = 0.15;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PitchAlgorithm {
+    #[default]
+    Dio,
+    Harvest,
+    Yin
+}
+
+impl PitchAlgorithm {
+    // The algorithm that runs in this build: Dio and Harvest need the `world` feature and fall back to YIN
+    // without it, YIN needs the `yin` feature. None if nothing can run.
+    pub fn effective(&self) -> Option<PitchAlgorithm> {
+        match self {
+            PitchAlgorithm::Dio | PitchAlgorithm::Harvest if cfg!(feature = "world") => Some(*self),
+            _ if cfg!(feature = "yin") => Some(PitchAlgorithm::Yin),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PitchConfig {
+    pub algorithm: PitchAlgorithm,
+
+    // Search range in Hz
+    pub f0_floor: f64,
+    pub f0_ceil: f64,
+
+    // Hop size between analysis frames in milliseconds
+    pub frame_period: f64,
+
+    // DIO downsampling factor (1-12), higher is faster but less accurate
+    pub speed: i32,
+
+    // Resample the audio to this rate before analysis
+    pub sample_rate: Option<u32>,
+
+    // Drop labels whose pitch could not be detected instead of keeping them with `pitch: None`
+    pub discard_unpitched: bool
+}
+
+impl Default for PitchConfig {
+    fn default() -> Self {
+        PitchConfig {
+            algorithm: PitchAlgorithm::Dio,
+            f0_floor: 71.0,
+            f0_ceil: 1760.0,
+            frame_period: 5.0,
+            speed: 1,
+            sample_rate: None,
+            discard_unpitched: false
+        }
+    }
+}
+
+#[cfg(any(feature = "world", feature = "yin"))]
+pub fn detect_pitch_from_samples(data: &[f64], sr: i32, config: &PitchConfig) -> Result<f64> {
+    let f0 = match config.algorithm.effective() {
+        #[cfg(feature = "world")]
+        Some(PitchAlgorithm::Dio) => {
+            let dio_option = DioOption {
+                f0_floor: config.f0_floor,
+                f0_ceil: config.f0_ceil,
+                frame_period: config.frame_period,
+                channels_in_octave: 2.0,
+                speed: config.speed,
+                allowed_range: 0.1,
+            };
+
+            // The WORLD bindings only take vectors
+            let data = data.to_vec();
+            let (t, rough_f0) = dio(&data, sr, &dio_option);
+            stonemask(&data, sr, &t, &rough_f0)
+        },
+        #[cfg(feature = "world")]
+        Some(PitchAlgorithm::Harvest) => {
+            let harvest_option = HarvestOption {
+                f0_floor: config.f0_floor,
+                f0_ceil: config.f0_ceil,
+                frame_period: config.frame_period,
+            };
+
+            let (_, f0) = harvest(&data.to_vec(), sr, &harvest_option);
+            f0
+        },
+        #[cfg(feature = "yin")]
+        Some(PitchAlgorithm::Yin) => yin(data, sr, config).1,
+        _ => bail!("Pitch algorithm {:?} is not available in this build", config.algorithm)
     };
 
-    let (t, rough_f0) = dio(data, sr, &dio_option);
-    let f0 = stonemask(data, sr, &t, &rough_f0);
-    let f0_avg = f0.iter().sum::<f64>() / f0.len() as f64;
+    // Average over voiced frames only; unvoiced frames are reported as 0 Hz
+    let voiced = f0.iter().filter(|f| **f > 0.0).collect::<Vec<&f64>>();
+    if voiced.is_empty() {
+        return Ok(f64::NAN);
+    }
 
-    f0_avg
+    Ok(voiced.iter().copied().sum::<f64>() / voiced.len() as f64)
 }
 
-pub fn write_pitch<P: AsRef<Path>>(file: P, config: &mut Vec<Utterance>) -> Result<()> {
-    // Make sure that the file is supported (wav only)
-    let file = file.as_ref();
-    let ext = file.extension().unwrap_or_default();
-    if ext != "wav" {
-        bail!("Unsupported file extension: {} ({})", ext.to_string_lossy(), file.display());
+// Pure-Rust YIN tracker, laid out like `dio`: frame i is centered at i * frame_period and the
+// result holds the temporal positions (in seconds) and f0 of each frame, with 0 for unvoiced frames.
+#[cfg(feature = "yin")]
+pub fn yin(data: &[f64], sr: i32, config: &PitchConfig) -> (Vec<f64>, Vec<f64>) {
+    let sr_f = sr as f64;
+    let tau_min = ((sr_f / config.f0_ceil).floor() as usize).max(2);
+    let tau_max = (sr_f / config.f0_floor).ceil() as usize;
+    let window = tau_max;
+
+    // Same frame count as WORLD's GetSamplesForDIO
+    let frames = (1000.0 * data.len() as f64 / sr_f / config.frame_period) as usize + 1;
+
+    let mut t = Vec::with_capacity(frames);
+    let mut f0 = Vec::with_capacity(frames);
+    let mut diff = vec![0.0; tau_max + 1];
+
+    for i in 0..frames {
+        let position = i as f64 * config.frame_period / 1000.0;
+        t.push(position);
+
+        // Frames whose analysis window does not fit inside the signal stay unvoiced
+        let center = (position * sr_f).round() as usize;
+        if tau_min >= tau_max || center < window / 2 || center - window / 2 + window + tau_max > data.len() {
+            f0.push(0.0);
+            continue;
+        }
+
+        let start = center - window / 2;
+        let frame = &data[start..start + window + tau_max];
+
+        // Difference function
+        for (tau, d) in diff.iter_mut().enumerate().skip(1) {
+            *d = (0..window).map(|j| (frame[j] - frame[j + tau]).powi(2)).sum();
+        }
+
+        // Cumulative mean normalized difference
+        diff[0] = 1.0;
+        let mut running = 0.0;
+        for (tau, d) in diff.iter_mut().enumerate().skip(1) {
+            running += *d;
+            *d = if running > 0.0 { *d * tau as f64 / running } else { 1.0 };
+        }
+
+        // Absolute threshold, then descend to the local minimum
+        let mut estimate = 0.0;
+        if let Some(mut tau) = (tau_min..tau_max).find(|tau| diff[*tau] < YIN_THRESHOLD) {
+            while tau + 1 < tau_max && diff[tau + 1] < diff[tau] {
+                tau += 1;
+            }
+
+            // Parabolic interpolation around the minimum
+            let (a, b, c) = (diff[tau - 1], diff[tau], diff[tau + 1]);
+            let denom = a + c - 2.0 * b;
+            let shift = if denom.abs() > f64::EPSILON { 0.5 * (a - c) / denom } else { 0.0 };
+
+            let freq = sr_f / (tau as f64 + shift);
+            if freq >= config.f0_floor && freq <= config.f0_ceil {
+                estimate = freq;
+            }
+        }
+
+        f0.push(estimate);
     }
 
-    // Read the file
-    let mut wav: Wav<f32> = Wav::from_path(file).unwrap();
-	let samples: Samples<f32> = wav.read().unwrap();
-    let sample_rate = wav.sample_rate() as f64;
+    (t, f0)
+}
 
-    let samples = samples.iter().map(|s| *s as f64).collect::<Vec<f64>>();
+#[cfg(feature = "generator-core")]
+pub fn write_pitch<P: AsRef<Path>>(file: P, config: &mut Vec<Utterance>, pitch_config: &PitchConfig) -> Result<()> {
+    // Read the file as mono
+    let audio = Audio::load(file, pitch_config.sample_rate)?;
+    let sample_rate = audio.sample_rate as f64;
+    let samples = audio.samples;
 
     // Get the pitch
     for utterance in config.iter_mut() {
-        if utterance.curr.eq("sil") {
+        utterance.pitch = None;
+
+        if PhonemeEncoder::is_silence(&utterance.curr) {
             continue;
         }
 
-        let start = utterance.start.samples(sample_rate).max(0);
-        let end = utterance.end.samples(sample_rate).clamp(0, samples.len());
-
-        assert!(start < end, "Start ({}) must be less than end ({}) (in {:?} @ {})", start, end, file, utterance.curr);
-        // println!("Path: {:?}, Start: {}, End: {}, label: {}", file, start, end, utterance.curr);
+        let range = utterance.range().samples(sample_rate);
+        let (start, end) = (range.start, range.end.min(samples.len()));
 
-        let pitch = detect_pitch_from_samples(&samples[start..end].to_vec(), wav.sample_rate());
-        let midi = ftom(pitch as f32);
-
-        if pitch.is_infinite() || pitch.is_nan() {
-            // println!("Pitch is infinite or NaN");
+        // Segments that are empty after rounding cannot be analysed, so they keep no pitch
+        if start >= end {
             continue;
         }
 
-        // println!("Pitch: {} / {}", pitch, midi);
+        let pitch = detect_pitch_from_samples(&samples[start..end], audio.sample_rate as i32, pitch_config)?;
+        if pitch.is_infinite() || pitch.is_nan() || pitch <= 0.0 {
+            continue;
+        }
 
-        utterance.pitch = Some(midi);
+        utterance.pitch = Some(ftom(pitch as f32));
     }
 
-    *config = config.iter().filter(|u| u.pitch.is_some()).cloned().collect();
+    if pitch_config.discard_unpitched {
+        config.retain(|u| u.pitch.is_some());
+        relink(config);
+    }
 
     Ok(())
 }
 
+// Rebuilds the prev/next chain after labels have been removed, so no label points at a phoneme that is gone.
+#[cfg(feature = "generator-core")]
+fn relink(config: &mut [Utterance]) {
+    let currs = config.iter().map(|u| u.curr.clone()).collect::<Vec<String>>();
+
+    for (i, utterance) in config.iter_mut().enumerate() {
+        utterance.prev = if i > 0 { currs[i - 1].clone() } else { String::from("sil") };
+        utterance.next = currs.get(i + 1).cloned().unwrap_or(String::from("sil"));
+    }
+}
+
+// Summarizes the pitch of a file as the median of its labels' pitches, falling back to a note
+// name in the file name or one of its folders (`ka_C4.wav`, `A3/ka.wav`).
+pub fn file_pitch<P: AsRef<Path>>(path: P, labels: &[Utterance]) -> Option<u8> {
+    let mut pitches = labels.iter().filter_map(|u| u.pitch).collect::<Vec<u8>>();
+    if pitches.is_empty() {
+        return infer_pitch_from_path(path);
+    }
+
+    pitches.sort();
+    Some(pitches[(pitches.len() - 1) / 2])
+}
+
+// Looks for a note name as the last `_`-separated part of the file stem, then in the parent folders from the innermost outwards.
+pub fn infer_pitch_from_path<P: AsRef<Path>>(path: P) -> Option<u8> {
+    let path = path.as_ref();
+
+    let suffix = path.file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.rsplit('_').next())
+        .and_then(note_to_midi);
+    if suffix.is_some() {
+        return suffix;
+    }
+
+    path.ancestors()
+        .skip(1)
+        .filter_map(|p| p.file_name().and_then(|s| s.to_str()))
+        .find_map(note_to_midi)
+}
+
+// Parses scientific pitch notation (`C4`, `F#3`, `Bb2`, `C-1`) into a MIDI note number, with C4 = 60.
+pub fn note_to_midi(name: &str) -> Option<u8> {
+    let mut chars = name.chars();
+
+    let class = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None
+    };
+
+    let rest = chars.as_str();
+    let (accidental, octave) = if let Some(r) = rest.strip_prefix('#') {
+        (1, r)
+    } else if let Some(r) = rest.strip_prefix('b') {
+        (-1, r)
+    } else {
+        (0, rest)
+    };
+
+    let octave = octave.parse::<i32>().ok()?;
+    let midi = (octave + 1) * 12 + class + accidental;
+
+    u8::try_from(midi).ok().filter(|m| *m <= 127)
+}
+
 pub fn mtof(midi: u8) -> f32 {
     440.0 * 2.0_f32.powf((midi as f32 - 69.0) / 12.0)
 }
@@ -77,3 +301,76 @@ pub fn mtof(midi: u8) -> f32 {
 pub fn ftom(freq: f32) -> u8 {
     (69.0 + 12.0 * (freq / 440.0).log2()).round() as u8
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_to_midi() {
+        assert_eq!(note_to_midi("C4"), Some(60));
+        assert_eq!(note_to_midi("A4"), Some(69));
+        assert_eq!(note_to_midi("F#3"), Some(54));
+        assert_eq!(note_to_midi("Bb2"), Some(46));
+        assert_eq!(note_to_midi("C-1"), Some(0));
+        assert_eq!(note_to_midi("G10"), None);
+        assert_eq!(note_to_midi("ka"), None);
+    }
+
+    #[test]
+    fn test_infer_pitch_from_path() {
+        assert_eq!(infer_pitch_from_path("bank/ka_C4.wav"), Some(60));
+        assert_eq!(infer_pitch_from_path("bank/A3/ka.wav"), Some(57));
+        assert_eq!(infer_pitch_from_path("bank/A3/ka_D4.wav"), Some(62));
+        assert_eq!(infer_pitch_from_path("bank/normal/ka.wav"), None);
+    }
+
+    #[test]
+    fn test_file_pitch_median() {
+        let labels = [Some(60), None, Some(62), Some(61)].iter().map(|p| Utterance {
+            pitch: *p,
+            ..Default::default()
+        }).collect::<Vec<Utterance>>();
+
+        assert_eq!(file_pitch("bank/A3/ka.wav", &labels), Some(61));
+        assert_eq!(file_pitch("bank/A3/ka.wav", &[]), Some(57));
+    }
+
+    #[cfg(feature = "yin")]
+    #[test]
+    fn test_yin_sine() {
+        let sr = 44100;
+        let freq = 220.0;
+        let data = (0..sr / 4).map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / sr as f64).sin()).collect::<Vec<f64>>();
+
+        let config = PitchConfig {
+            algorithm: PitchAlgorithm::Yin,
+            ..Default::default()
+        };
+
+        let pitch = detect_pitch_from_samples(&data, sr, &config).unwrap();
+        assert!((pitch - freq).abs() < 1.0, "Detected {} Hz", pitch);
+        assert_eq!(ftom(pitch as f32), 57);
+
+        // Without the WORLD bindings Dio runs as YIN
+        if !cfg!(feature = "world") {
+            assert_eq!(PitchAlgorithm::Dio.effective(), Some(PitchAlgorithm::Yin));
+        }
+    }
+
+    #[cfg(feature = "yin")]
+    #[test]
+    fn test_yin_frame_layout() {
+        let sr = 48000;
+        let data = vec![0.0; sr as usize];
+
+        let config = PitchConfig::default();
+        let (t, f0) = yin(&data, sr, &config);
+
+        // 1 second at a 5 ms frame period, like dio
+        assert_eq!(t.len(), 201);
+        assert_eq!(f0.len(), 201);
+        assert_eq!(t[1], 0.005);
+        assert!(f0.iter().all(|f| *f == 0.0));
+    }
+}
@@ -0,0 +1,219 @@
+// Recording lists for the units a bank is missing. Entries are built greedily: each one starts and ends in
+// silence and keeps appending the phoneme that completes the most missing units, so few entries cover many gaps.
+
+use std::collections::BTreeSet;
+
+use uuid::Uuid;
+
+use crate::{encode::PhonemeEncoder, library::Library, tools::coverage::{Coverage, CoverageStatus, CoverageTarget, Unit, UnitKind}, utterance::{FileDescriptor, Utterance}, Singer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReclistConfig {
+    // Phonemes per entry, not counting the surrounding silence
+    pub max_length: usize,
+    // Units recorded fewer times than this are recorded again
+    pub min_count: usize
+}
+
+impl Default for ReclistConfig {
+    fn default() -> Self {
+        ReclistConfig {
+            max_length: 8,
+            min_count: 1
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Reclist {
+    pub entries: Vec<Vec<String>>,
+    // Units no entry could cover, e.g. triphones when `max_length` is below 3
+    pub uncovered: BTreeSet<Unit>
+}
+
+impl Reclist {
+    // Covers the target units the singer has recorded fewer than `min_count` times
+    pub fn for_singer(singer: &Singer, target: &CoverageTarget, config: &ReclistConfig) -> Self {
+        let report = Coverage::from_singer(singer).compare(target, config.min_count);
+        let missing = report.entries.into_iter()
+            .filter(|e| e.status != CoverageStatus::Covered)
+            .map(|e| e.unit)
+            .collect();
+
+        Self::generate(missing, config)
+    }
+
+    pub fn generate(mut missing: BTreeSet<Unit>, config: &ReclistConfig) -> Self {
+        let alphabet = missing.iter()
+            .flat_map(|u| u.phonemes.iter())
+            .filter(|p| !PhonemeEncoder::is_silence(p))
+            .cloned()
+            .collect::<BTreeSet<String>>();
+
+        let mut entries = vec![];
+        loop {
+            let entry = next_entry(&mut missing, &alphabet, config.max_length);
+            if entry.is_empty() {
+                break;
+            }
+
+            entries.push(entry);
+        }
+
+        Reclist {
+            entries,
+            uncovered: missing
+        }
+    }
+
+    // One entry per line, phonemes separated by spaces
+    pub fn to_plain(&self) -> String {
+        self.entries.iter().map(|e| e.join(" ") + "\n").collect()
+    }
+
+    // A library with one file per entry, its line as the alias and unaligned labels surrounded by silence
+    pub fn to_library(&self, name: &str) -> Library {
+        let files = self.entries.iter().enumerate().map(|(i, entry)| {
+            let phonemes = std::iter::once("sil")
+                .chain(entry.iter().map(|p| p.as_str()))
+                .chain(std::iter::once("sil"))
+                .collect::<Vec<&str>>();
+
+            let labels = phonemes.iter().enumerate().map(|(j, curr)| Utterance {
+                prev: if j > 0 { phonemes[j - 1].to_string() } else { String::from("sil") },
+                curr: curr.to_string(),
+                next: phonemes.get(j + 1).unwrap_or(&"sil").to_string(),
+                ..Default::default()
+            }).collect();
+
+            FileDescriptor {
+                path: format!("{:03}.wav", i + 1).into(),
+                aliases: vec![entry.join(" ")],
+                labels,
+                ..Default::default()
+            }
+        }).collect();
+
+        Library {
+            name: name.to_string(),
+            uuid: Uuid::new_v4(),
+            files,
+            ..Default::default()
+        }
+    }
+}
+
+// Builds one entry and removes the units it covers. Returns an empty entry once nothing more can be covered.
+fn next_entry(missing: &mut BTreeSet<Unit>, alphabet: &BTreeSet<String>, max_length: usize) -> Vec<String> {
+    let before = missing.len();
+    let mut entry: Vec<String> = vec![];
+
+    // Units completed by placing `next` after the entry so far
+    let completed = |entry: &[String], next: &str| {
+        let curr = entry.last().map_or("sil", |p| p.as_str());
+        let prev = if entry.len() > 1 { entry[entry.len() - 2].as_str() } else { "sil" };
+
+        let mut units = vec![];
+        if !PhonemeEncoder::is_silence(next) {
+            units.push(Unit::new(UnitKind::Monophone, &[next]));
+        }
+        if !PhonemeEncoder::is_silence(curr) {
+            units.push(Unit::new(UnitKind::Diphone, &[curr, next]));
+            units.push(Unit::new(UnitKind::Triphone, &[prev, curr, next]));
+        }
+
+        units
+    };
+
+    while entry.len() < max_length {
+        let gain = |next: &str, missing: &BTreeSet<Unit>| completed(&entry, next).iter().filter(|u| missing.contains(*u)).count();
+
+        let mut best: Option<(&String, usize)> = None;
+        for phoneme in alphabet.iter() {
+            let g = gain(phoneme, missing);
+            if g > 0 && best.is_none_or(|(_, b)| g > b) {
+                best = Some((phoneme, g));
+            }
+        }
+
+        if best.is_none() && gain("sil", missing) > 0 {
+            break;
+        }
+
+        // Nothing completes a unit right now: move to a phoneme that starts one
+        if best.is_none() {
+            let curr = entry.last().map_or("sil", |p| p.as_str());
+            best = alphabet.iter()
+                .map(|p| (p, missing.iter().filter(|u| starts_with(u, curr, p)).count()))
+                .filter(|(_, g)| *g > 0)
+                .max_by_key(|(_, g)| *g);
+        }
+
+        // Nor starts one: jump to the first phoneme of a triphone that does not begin in silence
+        if best.is_none() {
+            best = missing.iter()
+                .filter(|u| u.kind == UnitKind::Triphone && !PhonemeEncoder::is_silence(&u.phonemes[0]))
+                .find_map(|u| alphabet.get(&u.phonemes[0]))
+                .map(|p| (p, 0));
+        }
+
+        let Some((phoneme, _)) = best else {
+            break;
+        };
+
+        for unit in completed(&entry, phoneme) {
+            missing.remove(&unit);
+        }
+        entry.push(phoneme.clone());
+    }
+
+    for unit in completed(&entry, "sil") {
+        missing.remove(&unit);
+    }
+
+    if missing.len() == before {
+        return vec![];
+    }
+
+    entry
+}
+
+// Whether placing `phoneme` after `curr` makes the unit reachable with the next phoneme
+fn starts_with(unit: &Unit, curr: &str, phoneme: &str) -> bool {
+    match unit.kind {
+        UnitKind::Monophone => false,
+        UnitKind::Diphone => unit.phonemes[0] == phoneme,
+        UnitKind::Triphone => unit.phonemes[0] == curr && unit.phonemes[1] == phoneme
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_reclist() {
+        let target = CoverageTarget::from_inventory(&["k", "a", "i"]);
+        let reclist = Reclist::generate(target.units.clone(), &ReclistConfig::default());
+
+        assert!(reclist.uncovered.is_empty());
+
+        let library = reclist.to_library("Reclist");
+        let coverage = Coverage::from_library(&library);
+        assert_eq!(coverage.compare(&target, 1).missing().count(), 0);
+
+        // 3 monophones and 12 diphones fit in far fewer entries than one per unit
+        assert!(reclist.entries.len() <= 4);
+        assert_eq!(reclist.to_plain().lines().count(), reclist.entries.len());
+        assert_eq!(library.files[0].labels.first().unwrap().curr, "sil");
+    }
+
+    #[test]
+    fn test_reclist_reaches_mid_word_triphones() {
+        let missing = BTreeSet::from([Unit::new(UnitKind::Triphone, &["k", "a", "t"])]);
+        let reclist = Reclist::generate(missing, &ReclistConfig::default());
+
+        assert!(reclist.uncovered.is_empty());
+        assert_eq!(reclist.entries, vec![vec!["k", "a", "t"]]);
+    }
+}
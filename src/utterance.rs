@@ -1,13 +1,187 @@
 use std::{collections::HashMap, path::PathBuf};
 
+use anyhow::{Result, bail};
 use intbits::Bits;
 use serde::{Deserialize, Serialize};
 
-use crate::time::Timestamp;
+use crate::{encode::PhonemeEncoder, time::{TimeRange, Timestamp}};
+
+// Canonical keys of `Utterance::areas`, modelled on UTAU's oto parameters. Other keys may be stored alongside them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Area {
+    // Fixed region at the start of the label that is never stretched (UTAU's consonant)
+    Consonant,
+    // Region crossfaded with the end of the previous label
+    Overlap,
+    // From the start of the label to the point that lands on the note onset
+    Preutterance,
+    // Region that is stretched or looped to fit the note length
+    Vowel,
+    // Trailing region that is discarded
+    Cutoff
+}
+
+impl Area {
+    pub const ALL: [Area; 5] = [Area::Consonant, Area::Overlap, Area::Preutterance, Area::Vowel, Area::Cutoff];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Area::Consonant => "consonant",
+            Area::Overlap => "overlap",
+            Area::Preutterance => "preutterance",
+            Area::Vowel => "vowel",
+            Area::Cutoff => "cutoff"
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Area> {
+        Area::ALL.into_iter().find(|a| a.key() == key)
+    }
+}
+
+// X-SAMPA symbols by manner of articulation, without diacritics or length marks
+const VOWELS: [&str; 32] = ["i", "y", "1", "}", "M", "u", "I", "Y", "I\\", "U\\", "U", "e", "2", "@\\", "8", "7", "o", "@", "E", "9", "3", "3\\", "V", "O", "{", "6", "a", "&", "A", "Q", "@`", "3`"];
+// Diphthongs the ARPAbet and CZampa tables produce
+const DIPHTHONGS: [&str; 6] = ["aI", "aU", "eI", "oU", "OI", "eU"];
+const NASALS: [&str; 7] = ["m", "F", "n", "n`", "J", "N", "N\\"];
+const PLOSIVES: [&str; 12] = ["p", "b", "t", "d", "t`", "d`", "c", "J\\", "k", "g", "q", "G\\"];
+const FRICATIVES: [&str; 26] = ["p\\", "B", "f", "v", "T", "D", "s", "z", "S", "Z", "s`", "z`", "C", "j\\", "x", "G", "X", "R", "X\\", "?\\", "h", "h\\", "s\\", "z\\", "K", "K\\"];
+const AFFRICATES: [&str; 6] = ["ts", "dz", "tS", "dZ", "ts\\", "dz\\"];
+// Consonants that are voiced without a `_v` diacritic; vowels and nasals always are
+const VOICED: [&str; 35] = [
+    "b", "d", "d`", "J\\", "g", "G\\", "B", "v", "D", "z", "Z", "z`", "j\\", "G", "R", "?\\", "h\\", "z\\", "K\\",
+    "l", "l`", "L", "4", "r", "r`", "r\\", "r\\`", "j", "w", "H", "M\\", "P", "dz", "dZ", "dz\\"
+];
+
+// Typed view of `Utterance::flags`. Consonants are every classified phoneme that is not a vowel, silence or breath,
+// so they have no bit of their own and the eight stored flags fit the flag byte of the minified layout. They are
+// still a named field, derived on read.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PhonemeFlags {
+    bits: u8
+}
 
-pub struct PhonemeFlags;
 impl PhonemeFlags {
-    pub const IS_VOWEL: u8 = 0b00000001;
+    pub const VOWEL: PhonemeFlags = PhonemeFlags { bits: 1 << 0 };
+    pub const VOICED: PhonemeFlags = PhonemeFlags { bits: 1 << 1 };
+    pub const NASAL: PhonemeFlags = PhonemeFlags { bits: 1 << 2 };
+    pub const PLOSIVE: PhonemeFlags = PhonemeFlags { bits: 1 << 3 };
+    pub const FRICATIVE: PhonemeFlags = PhonemeFlags { bits: 1 << 4 };
+    pub const SILENCE: PhonemeFlags = PhonemeFlags { bits: 1 << 5 };
+    pub const BREATH: PhonemeFlags = PhonemeFlags { bits: 1 << 6 };
+    pub const GLOTTAL_STOP: PhonemeFlags = PhonemeFlags { bits: 1 << 7 };
+
+    #[deprecated(note = "use `PhonemeFlags::VOWEL`")]
+    pub const IS_VOWEL: u8 = Self::VOWEL.bits;
+
+    // The derived field, listed after the stored ones
+    pub const CONSONANT: &'static str = "consonant";
+
+    // The stored flags in bit order
+    pub const NAMES: [(PhonemeFlags, &'static str); 8] = [
+        (Self::VOWEL, "vowel"), (Self::VOICED, "voiced"), (Self::NASAL, "nasal"), (Self::PLOSIVE, "plosive"),
+        (Self::FRICATIVE, "fricative"), (Self::SILENCE, "silence"), (Self::BREATH, "breath"), (Self::GLOTTAL_STOP, "glottal_stop")
+    ];
+
+    pub fn empty() -> Self {
+        PhonemeFlags::default()
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        PhonemeFlags { bits }
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    pub fn contains(&self, other: PhonemeFlags) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    pub fn insert(&mut self, other: PhonemeFlags) {
+        self.bits |= other.bits;
+    }
+
+    pub fn remove(&mut self, other: PhonemeFlags) {
+        self.bits &= !other.bits;
+    }
+
+    pub fn is_consonant(&self) -> bool {
+        !self.contains(Self::VOWEL) && !self.contains(Self::SILENCE) && !self.contains(Self::BREATH) && self.bits != 0
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        let consonant = Some(Self::CONSONANT).filter(|_| self.is_consonant());
+        Self::NAMES.iter().filter(|(f, _)| self.contains(*f)).map(|(_, n)| *n).chain(consonant).collect()
+    }
+
+    // As stored in `Singer::flag_fields`: one name per bit, then `consonant`
+    pub fn field_names() -> Vec<String> {
+        Self::NAMES.iter().map(|(_, n)| *n).chain([Self::CONSONANT]).map(|n| n.to_string()).collect()
+    }
+
+    // Whether the field from `field_names` is set, or None for an unknown name
+    pub fn field(&self, name: &str) -> Option<bool> {
+        if name == Self::CONSONANT {
+            return Some(self.is_consonant());
+        }
+
+        Self::NAMES.iter().find(|(_, n)| *n == name).map(|(f, _)| self.contains(*f))
+    }
+
+    // Classifies an X-SAMPA symbol. Unknown symbols get no flags.
+    pub fn classify(symbol: &str) -> PhonemeFlags {
+        match symbol {
+            "br" => return Self::BREATH,
+            s if PhonemeEncoder::is_silence(s) => return Self::SILENCE,
+            _ => {}
+        }
+
+        // Diacritics follow an underscore; length, nasalization and syllabicity marks are trailing characters
+        let (base, diacritics) = symbol.split_once('_').unwrap_or((symbol, ""));
+        let base = base.trim_end_matches(":\\").trim_end_matches([':', '~', '=']);
+
+        let mut flags = PhonemeFlags::empty();
+        if VOWELS.contains(&base) || DIPHTHONGS.contains(&base) {
+            flags = Self::VOWEL | Self::VOICED;
+        } else if NASALS.contains(&base) {
+            flags = Self::NASAL | Self::VOICED;
+        } else if base == "?" {
+            flags = Self::GLOTTAL_STOP | Self::PLOSIVE;
+        } else if PLOSIVES.contains(&base) {
+            flags = Self::PLOSIVE;
+        } else if FRICATIVES.contains(&base) {
+            flags = Self::FRICATIVE;
+        } else if AFFRICATES.contains(&base) {
+            flags = Self::PLOSIVE | Self::FRICATIVE;
+        } else if !VOICED.contains(&base) {
+            return flags;
+        }
+
+        if VOICED.contains(&base) {
+            flags.insert(Self::VOICED);
+        }
+
+        // `_0` devoices, `_v` voices
+        if diacritics.contains('0') {
+            flags.remove(Self::VOICED);
+        } else if diacritics.contains('v') {
+            flags.insert(Self::VOICED);
+        }
+
+        flags
+    }
+}
+
+impl std::ops::BitOr for PhonemeFlags {
+    type Output = PhonemeFlags;
+
+    fn bitor(self, other: PhonemeFlags) -> PhonemeFlags {
+        PhonemeFlags {
+            bits: self.bits | other.bits
+        }
+    }
 }
 
 
@@ -51,7 +225,7 @@ pub struct Utterance {
     pub flags: u8,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub areas: Option<HashMap<String, [Timestamp; 2]>>,
+    pub areas: Option<HashMap<String, TimeRange>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub minified: Option<[i64; 3]>
 }
@@ -74,7 +248,110 @@ impl Utterance {
         utterance
     }
 
+    pub fn range(&self) -> TimeRange {
+        TimeRange {
+            start: self.start,
+            end: self.end
+        }
+    }
+
+    pub fn set_range(&mut self, range: TimeRange) {
+        self.start = range.start;
+        self.end = range.end;
+    }
+
     pub fn length(&self) -> Timestamp {
-        self.end - self.start
+        self.range().length()
+    }
+
+    pub fn phoneme_flags(&self) -> PhonemeFlags {
+        PhonemeFlags::from_bits(self.flags)
+    }
+
+    // Fills `flags` from `curr`, which has to be in X-SAMPA
+    pub fn classify(&mut self) {
+        self.flags = PhonemeFlags::classify(&self.curr).bits();
+    }
+
+    pub fn area(&self, area: Area) -> Option<TimeRange> {
+        self.areas.as_ref().and_then(|a| a.get(area.key())).copied()
+    }
+
+    pub fn set_area(&mut self, area: Area, range: TimeRange) {
+        self.areas.get_or_insert_with(HashMap::new).insert(area.key().to_string(), range);
+    }
+
+    pub fn remove_area(&mut self, area: Area) -> Option<TimeRange> {
+        let range = self.areas.as_mut().and_then(|a| a.remove(area.key()));
+        if self.areas.as_ref().is_some_and(|a| a.is_empty()) {
+            self.areas = None;
+        }
+
+        range
+    }
+
+    // Every area, named or not, has to lie within the label
+    pub fn validate_areas(&self) -> Result<()> {
+        let bounds = self.range();
+
+        for (key, range) in self.areas.iter().flatten() {
+            if !bounds.contains_range(range) {
+                bail!("Area {} of label {} ({}..{}) lies outside the label ({}..{})", key, self.curr, range.start, range.end, bounds.start, bounds.end);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_areas() {
+        let mut utterance = Utterance {
+            curr: String::from("ka"),
+            start: Timestamp::new(0),
+            end: Timestamp::new(500),
+            ..Default::default()
+        };
+
+        utterance.set_area(Area::Consonant, TimeRange::new(Timestamp::new(0), Timestamp::new(120)));
+        utterance.set_area(Area::Vowel, TimeRange::new(Timestamp::new(120), Timestamp::new(450)));
+        assert!(utterance.validate_areas().is_ok());
+        assert_eq!(utterance.area(Area::Consonant).unwrap().length(), Timestamp::new(120));
+        assert_eq!(Area::from_key("vowel"), Some(Area::Vowel));
+
+        utterance.set_area(Area::Cutoff, TimeRange::new(Timestamp::new(450), Timestamp::new(600)));
+        assert!(utterance.validate_areas().is_err());
+
+        for area in Area::ALL {
+            utterance.remove_area(area);
+        }
+        assert!(utterance.areas.is_none());
+    }
+
+    #[test]
+    fn test_classify_flags() {
+        let classify = |s| PhonemeFlags::classify(s);
+
+        assert_eq!(classify("a:"), PhonemeFlags::VOWEL | PhonemeFlags::VOICED);
+        assert_eq!(classify("N\\"), PhonemeFlags::NASAL | PhonemeFlags::VOICED);
+        assert_eq!(classify("t_h"), PhonemeFlags::PLOSIVE);
+        assert_eq!(classify("dZ"), PhonemeFlags::PLOSIVE | PhonemeFlags::FRICATIVE | PhonemeFlags::VOICED);
+        assert_eq!(classify("z_0"), PhonemeFlags::FRICATIVE);
+        assert_eq!(classify("?"), PhonemeFlags::GLOTTAL_STOP | PhonemeFlags::PLOSIVE);
+        assert_eq!(classify("4"), PhonemeFlags::VOICED);
+        assert_eq!(classify("br"), PhonemeFlags::BREATH);
+        assert_eq!(classify("pau"), PhonemeFlags::SILENCE);
+
+        assert!(classify("4").is_consonant());
+        assert!(!classify("sil").is_consonant());
+        assert_eq!(classify("s").names(), vec!["fricative", "consonant"]);
+        assert_eq!(classify("k").field("consonant"), Some(true));
+        assert_eq!(classify("a").field("consonant"), Some(false));
+        assert_eq!(classify("a").field("fortis"), None);
+        assert_eq!(PhonemeFlags::field_names().last().map(|n| n.as_str()), Some("consonant"));
     }
 }